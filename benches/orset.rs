@@ -0,0 +1,76 @@
+//! Compares the tombstone-based `ORSet` against the `ORSWOT` under churn, i.e.
+//! elements that are repeatedly added and removed.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use crdt::{CvRDT, ORSet, ORSWOT};
+
+const ELEMENTS: u32 = 100;
+
+/// Adds and removes every element `rounds` times on a single replica.
+fn churn_orset(rounds: u32) -> ORSet<u32, u32> {
+    let mut set = ORSet::new();
+    for _ in 0..rounds {
+        for value in 0..ELEMENTS {
+            set.add(value, 0);
+        }
+        for value in 0..ELEMENTS {
+            set.remove(value);
+        }
+    }
+    set
+}
+
+fn churn_orswot(rounds: u32) -> ORSWOT<u32, u32> {
+    let mut set = ORSWOT::new();
+    for _ in 0..rounds {
+        for value in 0..ELEMENTS {
+            set.add(value, 0);
+        }
+        for value in 0..ELEMENTS {
+            set.remove(value);
+        }
+    }
+    set
+}
+
+fn bench_churn(c: &mut Criterion) {
+    let mut group = c.benchmark_group("churn");
+    for &rounds in &[1, 10, 100] {
+        group.bench_with_input(BenchmarkId::new("ORSet", rounds), &rounds, |b, &rounds| {
+            b.iter(|| churn_orset(black_box(rounds)))
+        });
+        group.bench_with_input(BenchmarkId::new("ORSWOT", rounds), &rounds, |b, &rounds| {
+            b.iter(|| churn_orswot(black_box(rounds)))
+        });
+    }
+    group.finish();
+}
+
+/// Merges a churned replica into a fresh one, which has to take in all of
+/// its history: every tombstone for the `ORSet`, only the causal context for
+/// the `ORSWOT`.
+fn bench_merge(c: &mut Criterion) {
+    let mut group = c.benchmark_group("merge");
+    for &rounds in &[1, 10, 100] {
+        let orset = churn_orset(rounds);
+        group.bench_with_input(BenchmarkId::new("ORSet", rounds), &orset, |b, orset| {
+            b.iter(|| {
+                let mut replica = ORSet::new();
+                replica.merge(orset);
+                replica
+            })
+        });
+        let orswot = churn_orswot(rounds);
+        group.bench_with_input(BenchmarkId::new("ORSWOT", rounds), &orswot, |b, orswot| {
+            b.iter(|| {
+                let mut replica = ORSWOT::new();
+                replica.merge(orswot);
+                replica
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_churn, bench_merge);
+criterion_main!(benches);
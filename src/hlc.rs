@@ -0,0 +1,457 @@
+//! A hybrid logical clock, for timestamps that track wall-clock time but stay
+//! monotonic and causally consistent.
+
+use std::cmp::max;
+use std::error::Error;
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+
+/// The number of bits of a packed `HlcTimestamp` holding the logical counter.
+const LOGICAL_BITS: u32 = 16;
+
+/// A timestamp issued by a `HybridLogicalClock`: a physical time in
+/// milliseconds since the Unix epoch, and a logical counter ordering events
+/// within the same millisecond.
+///
+/// The LWW types take these timestamps directly, e.g.
+/// `register.set(value, clock.now(), replica)`. Timestamps also pack into a
+/// `u64` with the same ordering.
+///
+/// Serializes as a struct with `physical` and `logical` fields. Deserializing
+/// fails if `physical` is out of range.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawHlcTimestamp"))]
+pub struct HlcTimestamp {
+    physical: u64,
+    logical: u16,
+}
+
+/// An unvalidated `HlcTimestamp`, as deserialized.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawHlcTimestamp {
+    physical: u64,
+    logical: u16,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawHlcTimestamp> for HlcTimestamp {
+    type Error = TimestampRangeError;
+
+    fn try_from(raw: RawHlcTimestamp) -> Result<HlcTimestamp, TimestampRangeError> {
+        HlcTimestamp::try_new(raw.physical, raw.logical)
+    }
+}
+
+impl HlcTimestamp {
+    /// The greatest physical time a timestamp can hold, in milliseconds.
+    pub const MAX_PHYSICAL: u64 = (1 << (64 - LOGICAL_BITS)) - 1;
+
+    /// # Panics
+    ///
+    /// Panics if `physical > MAX_PHYSICAL`.
+    pub fn new(physical: u64, logical: u16) -> HlcTimestamp {
+        assert!(physical <= HlcTimestamp::MAX_PHYSICAL, "physical time out of range");
+        HlcTimestamp { physical, logical }
+    }
+
+    /// Returns an error if `physical > MAX_PHYSICAL`.
+    pub fn try_new(physical: u64, logical: u16) -> Result<HlcTimestamp, TimestampRangeError> {
+        if physical > HlcTimestamp::MAX_PHYSICAL {
+            return Err(TimestampRangeError { physical });
+        }
+        Ok(HlcTimestamp { physical, logical })
+    }
+
+    pub fn physical(&self) -> u64 {
+        self.physical
+    }
+
+    pub fn logical(&self) -> u16 {
+        self.logical
+    }
+
+    /// Returns the next timestamp at `physical`, after `logical`. Moves on to
+    /// the next millisecond if the logical counter is exhausted, failing if
+    /// that passes `MAX_PHYSICAL`.
+    fn tick(physical: u64, logical: Option<u16>) -> Result<HlcTimestamp, TimestampRangeError> {
+        match logical.map(|logical| logical.checked_add(1)) {
+            None => HlcTimestamp::try_new(physical, 0),
+            Some(Some(logical)) => HlcTimestamp::try_new(physical, logical),
+            Some(None) => HlcTimestamp::try_new(physical.saturating_add(1), 0),
+        }
+    }
+}
+
+impl From<HlcTimestamp> for u64 {
+    fn from(timestamp: HlcTimestamp) -> u64 {
+        // Every way of making a timestamp checks `physical` is in range, so
+        // no bits are shifted out.
+        debug_assert!(timestamp.physical <= HlcTimestamp::MAX_PHYSICAL);
+        (timestamp.physical << LOGICAL_BITS) | u64::from(timestamp.logical)
+    }
+}
+
+impl From<u64> for HlcTimestamp {
+    fn from(packed: u64) -> HlcTimestamp {
+        HlcTimestamp {
+            physical: packed >> LOGICAL_BITS,
+            logical: packed as u16,
+        }
+    }
+}
+
+/// A source of physical time, in milliseconds since the Unix epoch.
+///
+/// Implemented by `SystemClock` and by closures returning `u64`, so tests can
+/// drive a `HybridLogicalClock` with a fake clock.
+pub trait PhysicalClock {
+    fn now_millis(&self) -> u64;
+}
+
+/// The system's wall clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct SystemClock;
+
+impl PhysicalClock for SystemClock {
+    fn now_millis(&self) -> u64 {
+        // A clock set before the epoch reads as the epoch.
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_millis() as u64)
+    }
+}
+
+impl<F: Fn() -> u64> PhysicalClock for F {
+    fn now_millis(&self) -> u64 {
+        self()
+    }
+}
+
+/// The error returned when a physical time is greater than
+/// `HlcTimestamp::MAX_PHYSICAL`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimestampRangeError {
+    pub physical: u64,
+}
+
+impl fmt::Display for TimestampRangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "physical time {}ms is greater than the maximum of {}ms",
+            self.physical,
+            HlcTimestamp::MAX_PHYSICAL
+        )
+    }
+}
+
+impl Error for TimestampRangeError {}
+
+/// The error returned when a received timestamp is further ahead of the local
+/// physical clock than the clock's maximum drift.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockDriftError {
+    pub received: HlcTimestamp,
+    /// The local physical time when the timestamp was received.
+    pub physical: u64,
+    pub max_drift: u64,
+}
+
+impl fmt::Display for ClockDriftError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "received timestamp is {}ms ahead of the local clock, more than the maximum drift of {}ms",
+            self.received.physical - self.physical,
+            self.max_drift
+        )
+    }
+}
+
+impl Error for ClockDriftError {}
+
+/// The error returned by `HybridLogicalClock::update`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateError {
+    /// The received timestamp is too far ahead of the local physical clock.
+    Drift(ClockDriftError),
+    /// The clock would pass `HlcTimestamp::MAX_PHYSICAL`.
+    OutOfRange(TimestampRangeError),
+}
+
+impl fmt::Display for UpdateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UpdateError::Drift(err) => err.fmt(f),
+            UpdateError::OutOfRange(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for UpdateError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            UpdateError::Drift(err) => Some(err),
+            UpdateError::OutOfRange(err) => Some(err),
+        }
+    }
+}
+
+/// A hybrid logical clock (Kulkarni et al., 2014).
+///
+/// Issues timestamps that stay close to the physical clock, yet never go
+/// backwards even if the physical clock does, and are greater than every
+/// timestamp received through `update`. Timestamps from the same clock are
+/// unique; timestamps from different clocks may collide, which is why the LWW
+/// types break ties by replica ID.
+///
+/// A received timestamp from too far in the future would drag the clock, and
+/// every clock that hears from it, ahead of real time. `update` rejects
+/// timestamps more than the maximum drift ahead of the local physical clock.
+#[derive(Debug, Clone)]
+pub struct HybridLogicalClock<C: PhysicalClock = SystemClock> {
+    source: C,
+    max_drift: u64,
+    last: Option<HlcTimestamp>,
+}
+
+impl HybridLogicalClock<SystemClock> {
+    /// How far ahead of the local physical clock `new` accepts received
+    /// timestamps, in milliseconds.
+    pub const DEFAULT_MAX_DRIFT: u64 = 60_000;
+
+    /// Returns a clock reading the system clock, accepting timestamps up to
+    /// `DEFAULT_MAX_DRIFT` ahead of it.
+    pub fn new() -> HybridLogicalClock<SystemClock> {
+        HybridLogicalClock::with_source(SystemClock, Self::DEFAULT_MAX_DRIFT)
+    }
+}
+
+impl Default for HybridLogicalClock<SystemClock> {
+    fn default() -> HybridLogicalClock<SystemClock> {
+        HybridLogicalClock::new()
+    }
+}
+
+impl<C: PhysicalClock> HybridLogicalClock<C> {
+    /// Returns a clock reading `source`, accepting timestamps up to
+    /// `max_drift` milliseconds ahead of it.
+    pub fn with_source(source: C, max_drift: u64) -> HybridLogicalClock<C> {
+        HybridLogicalClock {
+            source,
+            max_drift,
+            last: None,
+        }
+    }
+
+    pub fn max_drift(&self) -> u64 {
+        self.max_drift
+    }
+
+    /// Returns a timestamp for a local event.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the clock would pass `HlcTimestamp::MAX_PHYSICAL`.
+    pub fn now(&mut self) -> HlcTimestamp {
+        self.try_now().expect("physical time out of range")
+    }
+
+    /// Returns a timestamp for a local event, or an error without modifying
+    /// the clock if it would pass `HlcTimestamp::MAX_PHYSICAL`.
+    pub fn try_now(&mut self) -> Result<HlcTimestamp, TimestampRangeError> {
+        let physical = self.source.now_millis();
+        let timestamp = match self.last {
+            Some(last) if last.physical >= physical => {
+                HlcTimestamp::tick(last.physical, Some(last.logical))
+            }
+            _ => HlcTimestamp::tick(physical, None),
+        }?;
+        self.last = Some(timestamp);
+        Ok(timestamp)
+    }
+
+    /// Advances the clock past a timestamp received from another replica,
+    /// returning a timestamp for the receive event, or an error without
+    /// modifying the clock if `received` is too far ahead or the clock would
+    /// pass `HlcTimestamp::MAX_PHYSICAL`.
+    pub fn update(&mut self, received: HlcTimestamp) -> Result<HlcTimestamp, UpdateError> {
+        let physical = self.source.now_millis();
+        if received.physical > physical.saturating_add(self.max_drift) {
+            let max_drift = self.max_drift;
+            return Err(UpdateError::Drift(ClockDriftError { received, physical, max_drift }));
+        }
+        let last = self.last.unwrap_or_default();
+        let latest = max(max(last.physical, received.physical), physical);
+        // Continue the logical counter of whichever timestamps are at the
+        // latest physical time.
+        let logical = match (last.physical == latest, received.physical == latest) {
+            (true, true) => Some(max(last.logical, received.logical)),
+            (true, false) if self.last.is_some() => Some(last.logical),
+            (false, true) => Some(received.logical),
+            _ => None,
+        };
+        let timestamp = HlcTimestamp::tick(latest, logical).map_err(UpdateError::OutOfRange)?;
+        self.last = Some(timestamp);
+        Ok(timestamp)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "serde")]
+    use crate::test_util::assert_roundtrip;
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    /// Returns a clock driven by the returned fake physical time.
+    fn fake_clock(max_drift: u64) -> (HybridLogicalClock<impl PhysicalClock>, Rc<Cell<u64>>) {
+        let time = Rc::new(Cell::new(1000));
+        let source = {
+            let time = time.clone();
+            move || time.get()
+        };
+        (HybridLogicalClock::with_source(source, max_drift), time)
+    }
+
+    #[test]
+    fn test_now() {
+        let (mut clock, time) = fake_clock(100);
+        assert_eq!(clock.now(), HlcTimestamp::new(1000, 0));
+        assert_eq!(clock.now(), HlcTimestamp::new(1000, 1));
+
+        // Timestamps keep increasing while the physical clock goes backwards.
+        time.set(900);
+        assert_eq!(clock.now(), HlcTimestamp::new(1000, 2));
+        time.set(1001);
+        assert_eq!(clock.now(), HlcTimestamp::new(1001, 0));
+    }
+
+    #[test]
+    fn test_update() {
+        let (mut clock_a, time_a) = fake_clock(100);
+        let (mut clock_b, time_b) = fake_clock(100);
+        time_b.set(1050);
+        let sent = clock_b.now();
+
+        // Receiving a timestamp from a faster clock moves past it.
+        let received = clock_a.update(sent).unwrap();
+        assert_eq!(received, HlcTimestamp::new(1050, 1));
+        assert!(clock_a.now() > received);
+
+        // Once the physical clock catches up, the logical counter resets.
+        time_a.set(1060);
+        assert_eq!(clock_a.update(sent).unwrap(), HlcTimestamp::new(1060, 0));
+
+        // Equal physical times continue the greater logical counter.
+        time_b.set(1060);
+        clock_b.update(HlcTimestamp::new(1060, 7)).unwrap();
+        assert_eq!(clock_b.now(), HlcTimestamp::new(1060, 9));
+        let received = clock_a.update(HlcTimestamp::new(1060, 3)).unwrap();
+        assert_eq!(received, HlcTimestamp::new(1060, 4));
+
+        // A fresh clock receiving an old timestamp uses its physical time.
+        let (mut clock_c, _) = fake_clock(100);
+        let received = clock_c.update(HlcTimestamp::new(10, 5)).unwrap();
+        assert_eq!(received, HlcTimestamp::new(1000, 0));
+    }
+
+    #[test]
+    fn test_max_drift() {
+        let (mut clock, time) = fake_clock(100);
+        assert_eq!(clock.max_drift(), 100);
+        let last = clock.now();
+        assert!(clock.update(HlcTimestamp::new(1100, 0)).is_ok());
+        let err = clock.update(HlcTimestamp::new(1200, 0)).unwrap_err();
+        assert!(matches!(err, UpdateError::Drift(ClockDriftError { physical: 1000, .. })));
+        assert_eq!(
+            err.to_string(),
+            "received timestamp is 200ms ahead of the local clock, more than the maximum drift of 100ms"
+        );
+
+        // A rejected timestamp doesn't move the clock.
+        assert!(clock.now() > last);
+        assert_eq!(clock.now().physical(), 1100);
+        time.set(1150);
+        assert!(clock.update(HlcTimestamp::new(1200, 0)).is_ok());
+    }
+
+    #[test]
+    fn test_logical_overflow() {
+        let (mut clock, _) = fake_clock(100);
+        let timestamp = clock.update(HlcTimestamp::new(1000, u16::MAX)).unwrap();
+        assert_eq!(timestamp, HlcTimestamp::new(1001, 0));
+        assert_eq!(clock.now(), HlcTimestamp::new(1001, 1));
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        let max = HlcTimestamp::MAX_PHYSICAL;
+        assert_eq!(HlcTimestamp::try_new(max, 0), Ok(HlcTimestamp::new(max, 0)));
+        assert_eq!(HlcTimestamp::try_new(max + 1, 0), Err(TimestampRangeError { physical: max + 1 }));
+
+        // Receiving the greatest timestamp leaves no room for the receive
+        // event, and doesn't move the clock.
+        let (mut clock, time) = fake_clock(u64::MAX);
+        let err = clock.update(HlcTimestamp::new(max, u16::MAX)).unwrap_err();
+        assert_eq!(err, UpdateError::OutOfRange(TimestampRangeError { physical: max + 1 }));
+        assert_eq!(clock.now(), HlcTimestamp::new(1000, 0));
+
+        // Nor can a physical clock past the range.
+        time.set(max + 1);
+        let err = clock.update(HlcTimestamp::new(1000, 0)).unwrap_err();
+        assert!(matches!(err, UpdateError::OutOfRange(_)));
+        assert_eq!(clock.try_now(), Err(TimestampRangeError { physical: max + 1 }));
+        time.set(max);
+        assert_eq!(clock.try_now(), Ok(HlcTimestamp::new(max, 0)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let timestamp = HlcTimestamp::new(1_700_000_000_000, 3);
+        let json = serde_json::to_string(&timestamp).unwrap();
+        assert_eq!(json, r#"{"physical":1700000000000,"logical":3}"#);
+        assert_roundtrip(&timestamp);
+        assert_roundtrip(&HlcTimestamp::new(HlcTimestamp::MAX_PHYSICAL, 1));
+
+        // An out of range timestamp would lose bits when packed.
+        let json = format!(r#"{{"physical":{},"logical":1}}"#, HlcTimestamp::MAX_PHYSICAL + 1);
+        let err = serde_json::from_str::<HlcTimestamp>(&json).unwrap_err();
+        assert!(err.to_string().starts_with("physical time"));
+    }
+
+    #[test]
+    fn test_packing() {
+        let timestamps = [
+            HlcTimestamp::new(0, 0),
+            HlcTimestamp::new(0, u16::MAX),
+            HlcTimestamp::new(1, 0),
+            HlcTimestamp::new(1_700_000_000_000, 42),
+            HlcTimestamp::new(HlcTimestamp::MAX_PHYSICAL, u16::MAX),
+        ];
+        for pair in timestamps.windows(2) {
+            assert!(u64::from(pair[0]) < u64::from(pair[1]));
+        }
+        for &timestamp in timestamps.iter() {
+            assert_eq!(HlcTimestamp::from(u64::from(timestamp)), timestamp);
+        }
+        assert_eq!(u64::from(HlcTimestamp::new(HlcTimestamp::MAX_PHYSICAL, u16::MAX)), u64::MAX);
+    }
+
+    #[test]
+    fn test_system_clock() {
+        let mut clock = HybridLogicalClock::new();
+        let first = clock.now();
+        assert!(first.physical() > 0);
+        assert!(clock.now() > first);
+    }
+}
@@ -1,83 +1,3010 @@
-use std::collections::HashMap;
+//! Conflict-free replicated data types.
+//!
+//! # Features
+//!
+//! - `serde`: derives `Serialize` and `Deserialize` for every CRDT and
+//!   operation type. The serialized layout of each type is documented on the
+//!   type and is kept stable across releases.
+
+use std::collections::{HashMap, HashSet};
 use std::convert::TryInto;
-use std::cmp::max;
+use std::cmp::{max, Ordering};
+use std::error::Error;
+use std::fmt;
+use std::hash::Hash;
+use std::iter::FromIterator;
 
-/// An eventually consistent distributed counter that only grows.
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+mod hlc;
+mod rga;
+mod text;
+pub mod wire;
+#[cfg(test)]
+mod test_util;
+
+pub use hlc::{
+    ClockDriftError, HlcTimestamp, HybridLogicalClock, PhysicalClock, SystemClock, TimestampRangeError, UpdateError,
+};
+pub use rga::{ElementId, RGAOp, RGAUpdate, RGA};
+pub use text::{CharSpan, Text, TextOp, TextUpdate};
+
+/// A state-based (convergent) replicated data type.
+///
+/// The states of a CvRDT form a join-semilattice: `merge` moves a replica to
+/// the least upper bound of two states, and `PartialOrd` exposes the lattice's
+/// partial order, so `other <= self` means merging `other` is a no-op.
+/// Replicas converge once they have merged each other's states.
+pub trait CvRDT: PartialOrd {
+    /// The type returned when querying a replica's state.
+    type Value;
+
+    /// Returns the value currently observed by this replica.
+    fn value(&self) -> Self::Value;
+
+    /// Joins `other` into this replica.
+    fn merge(&mut self, other: &Self);
+
+    /// Returns the join of two states, leaving both untouched.
+    fn join(&self, other: &Self) -> Self
+    where
+        Self: Clone,
+    {
+        let mut joined = self.clone();
+        joined.merge(other);
+        joined
+    }
+}
+
+/// An operation-based (commutative) replicated data type.
+///
+/// Updates happen in two phases. `prepare` runs at the replica where an update
+/// originates and turns it into an operation without modifying any state;
+/// `effect` then applies that operation at every replica, the source included.
+/// Concurrent operations commute, so replicas converge once each of them has
+/// applied every operation exactly once.
+pub trait CmRDT {
+    /// An update as requested at the source replica.
+    type Update;
+
+    /// An operation that can be shipped to and applied at any replica.
+    type Op;
+
+    /// Computes the operation for `update` from this replica's state.
+    fn prepare(&self, update: Self::Update) -> Self::Op;
+
+    /// Applies an operation produced by `prepare` on any replica.
+    fn effect(&mut self, op: Self::Op);
+}
+
+/// A buffer of delta-states produced by a replica's delta mutators.
+///
+/// Deltas are states of the same lattice as the CRDT that produced them, so a
+/// delta-group is simply the join of the buffered deltas. It can be shipped to
+/// other replicas as a whole and applied there with `CvRDT::merge`.
+///
+/// Serializes as the delta-group itself, or `null` when empty.
 #[derive(Debug)]
-pub struct GCounter {
-    /// Map from ReplicaID to the replica's local count.
-    counters: HashMap<String, u64>,
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct DeltaGroup<T> {
+    group: Option<T>,
+}
+
+impl<T: CvRDT> DeltaGroup<T> {
+    pub fn new() -> DeltaGroup<T> {
+        DeltaGroup { group: None }
+    }
+
+    /// Joins `delta` into the group.
+    pub fn push(&mut self, delta: T) {
+        match self.group.as_mut() {
+            Some(group) => group.merge(&delta),
+            None => self.group = Some(delta),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.group.is_none()
+    }
+
+    /// Returns the join of every delta pushed so far, leaving the group empty.
+    pub fn take(&mut self) -> Option<T> {
+        self.group.take()
+    }
+}
+
+impl<T: CvRDT> Default for DeltaGroup<T> {
+    fn default() -> DeltaGroup<T> {
+        DeltaGroup::new()
+    }
+}
+
+/// Identifies a replica, e.g. an integer node ID, a UUID or an interned string.
+///
+/// Implemented for every type with the required bounds.
+pub trait ReplicaId: Hash + Eq + Ord + Clone {}
+
+impl<T: Hash + Eq + Ord + Clone> ReplicaId for T {}
+
+/// The error returned when a counter's state or value doesn't fit in its
+/// integer type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverflowError;
+
+impl fmt::Display for OverflowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "counter overflow")
+    }
+}
+
+impl Error for OverflowError {}
+
+/// Combines the orderings of two independent components of a product lattice.
+fn combine_orderings(a: Option<Ordering>, b: Option<Ordering>) -> Option<Ordering> {
+    match (a?, b?) {
+        (Ordering::Equal, o) | (o, Ordering::Equal) => Some(o),
+        (x, y) if x == y => Some(x),
+        _ => None,
+    }
+}
+
+/// Merges two maps entry by entry, keeping the greater value of each entry.
+fn merge_max<K: Hash + Eq + Clone, V: Ord + Copy>(local: &mut HashMap<K, V>, remote: &HashMap<K, V>) {
+    for (k, &v_remote) in remote.iter() {
+        if let Some(v_local) = local.get_mut(k) {
+            *v_local = max(*v_local, v_remote);
+        } else {
+            local.insert(k.clone(), v_remote);
+        }
+    }
+}
+
+/// Serializes a map keyed by elements as a list of `[key, value]` pairs, since
+/// formats such as JSON only allow string keys. Fields serialized with it need
+/// a `bound` attribute, as serde doesn't infer bounds through `with`.
+#[cfg(feature = "serde")]
+mod map_as_pairs {
+    use std::collections::HashMap;
+    use std::hash::Hash;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<K, V, S>(map: &HashMap<K, V>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        V: Serialize,
+        S: Serializer,
+    {
+        serializer.collect_seq(map.iter())
+    }
+
+    pub fn deserialize<'de, K, V, D>(deserializer: D) -> Result<HashMap<K, V>, D::Error>
+    where
+        K: Deserialize<'de> + Hash + Eq,
+        V: Deserialize<'de>,
+        D: Deserializer<'de>,
+    {
+        let pairs: Vec<(K, V)> = Vec::deserialize(deserializer)?;
+        Ok(pairs.into_iter().collect())
+    }
+}
+
+/// A version vector, counting the events seen from each replica.
+///
+/// Version vectors are partially ordered: `a <= b` if `b` has seen at least as
+/// many events as `a` from every replica. Replicas missing from the vector
+/// count as zero.
+///
+/// Serializes as a map from replica IDs to counters.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct VersionVector<R: ReplicaId> {
+    counters: HashMap<R, u64>,
+}
+
+impl<R: ReplicaId> VersionVector<R> {
+    pub fn new() -> VersionVector<R> {
+        VersionVector { counters: HashMap::new() }
+    }
+
+    /// Returns the number of events seen from `replica`.
+    pub fn get(&self, replica: &R) -> u64 {
+        self.counters.get(replica).copied().unwrap_or(0)
+    }
+
+    /// Returns the replicas seen along with their counters, in no particular
+    /// order.
+    pub fn iter(&self) -> impl Iterator<Item = (&R, u64)> {
+        self.counters.iter().map(|(replica, &counter)| (replica, counter))
+    }
+
+    /// Records a new event from `replica`, returning its counter.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the counter overflows a `u64`.
+    pub fn increment(&mut self, replica: R) -> u64 {
+        let counter = self.counters.entry(replica).or_insert(0);
+        *counter = counter.checked_add(1).expect("overflow");
+        *counter
+    }
+
+    /// Advances every counter to the greater of the two vectors' counters.
+    pub fn merge(&mut self, other: &VersionVector<R>) {
+        merge_max(&mut self.counters, &other.counters);
+    }
+
+    /// Returns whether `self` has seen every event `other` has, and more.
+    pub fn dominates(&self, other: &VersionVector<R>) -> bool {
+        self > other
+    }
+
+    /// Returns whether `other` has seen every event `self` has, and more.
+    pub fn happened_before(&self, other: &VersionVector<R>) -> bool {
+        self < other
+    }
+
+    /// Returns whether each vector has seen events the other hasn't.
+    pub fn concurrent_with(&self, other: &VersionVector<R>) -> bool {
+        self.partial_cmp(other).is_none()
+    }
+
+    /// Sets `replica`'s counter, returning the previous one if it was present.
+    fn insert(&mut self, replica: R, counter: u64) -> Option<u64> {
+        self.counters.insert(replica, counter)
+    }
+
+    /// Returns the dot of `replica`'s next event, without recording it.
+    fn next_dot(&self, replica: R) -> Dot<R> {
+        let counter = self.get(&replica) + 1;
+        Dot { replica, counter }
+    }
+
+    /// Advances the vector to include `dot`.
+    fn observe(&mut self, dot: &Dot<R>) {
+        if self.get(&dot.replica) < dot.counter {
+            self.counters.insert(dot.replica.clone(), dot.counter);
+        }
+    }
+}
+
+impl<R: ReplicaId> Default for VersionVector<R> {
+    fn default() -> VersionVector<R> {
+        VersionVector::new()
+    }
+}
+
+impl<R: ReplicaId> PartialEq for VersionVector<R> {
+    fn eq(&self, other: &VersionVector<R>) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl<R: ReplicaId> Eq for VersionVector<R> {}
+
+impl<R: ReplicaId> PartialOrd for VersionVector<R> {
+    fn partial_cmp(&self, other: &VersionVector<R>) -> Option<Ordering> {
+        let mut ordering = Ordering::Equal;
+        for k in self.counters.keys().chain(other.counters.keys()) {
+            ordering = combine_orderings(Some(ordering), Some(self.get(k).cmp(&other.get(k))))?;
+        }
+        Some(ordering)
+    }
+}
+
+/// An eventually consistent distributed counter that only grows.
+///
+/// The state is a `VersionVector` holding each replica's local count, and the
+/// value is their sum.
+///
+/// Serializes as a struct with a single `counters` field, mapping each replica
+/// ID to that replica's count.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GCounter<R: ReplicaId> {
+    /// Each replica's local count.
+    counters: VersionVector<R>,
+}
+
+impl<R: ReplicaId> GCounter<R> {
+    pub fn new() -> GCounter<R> {
+        GCounter {
+            counters: VersionVector::new(),
+        }
+    }
+
+    /// Returns the counter's value, or `None` if it overflows a `u64`.
+    pub fn checked_value(&self) -> Option<u64> {
+        self.try_value().ok()
+    }
+
+    /// Returns the counter's value, or an error if it overflows a `u64`.
+    pub fn try_value(&self) -> Result<u64, OverflowError> {
+        self.wide_value().try_into().map_err(|_| OverflowError)
+    }
+
+    /// Sums the replica counts without overflowing.
+    fn wide_value(&self) -> u128 {
+        self.counters.iter().map(|(_, v)| u128::from(v)).sum()
+    }
+
+    /// Increments `replica`'s count, returning the operation to replicate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `replica`'s count overflows a `u64`; see `try_inc`.
+    pub fn inc(&mut self, replica: R, count: u64) -> GCounterOp<R> {
+        let op = self.prepare(GCounterOp { replica, count });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Increments `replica`'s count, or returns an error without modifying the
+    /// counter if the count would overflow a `u64`.
+    pub fn try_inc(&mut self, replica: R, count: u64) -> Result<GCounterOp<R>, OverflowError> {
+        self.counters.get(&replica).checked_add(count).ok_or(OverflowError)?;
+        Ok(self.inc(replica, count))
+    }
+
+    /// Increments `replica`'s count, returning a delta-state that carries
+    /// just the updated entry.
+    pub fn inc_delta(&mut self, replica: R, count: u64) -> GCounter<R> {
+        self.inc(replica.clone(), count);
+        let mut delta = GCounter::new();
+        let local = self.counters.get(&replica);
+        delta.counters.insert(replica, local);
+        delta
+    }
+}
+
+impl<R: ReplicaId> Default for GCounter<R> {
+    fn default() -> GCounter<R> {
+        GCounter::new()
+    }
+}
+
+/// An operation on a `GCounter`: add `count` to `replica`'s local count.
+///
+/// Serializes as a struct with `replica` and `count` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GCounterOp<R> {
+    pub replica: R,
+    pub count: u64,
+}
+
+impl<R: ReplicaId> CmRDT for GCounter<R> {
+    type Update = GCounterOp<R>;
+    type Op = GCounterOp<R>;
+
+    fn prepare(&self, update: GCounterOp<R>) -> GCounterOp<R> {
+        update
+    }
+
+    fn effect(&mut self, op: GCounterOp<R>) {
+        let GCounterOp { replica, count } = op;
+        let local = self.counters.get(&replica);
+        self.counters.insert(replica, local.checked_add(count).expect("overflow"));
+    }
+}
+
+impl<R: ReplicaId> CvRDT for GCounter<R> {
+    type Value = u64;
+
+    /// # Panics
+    ///
+    /// Panics if the value overflows a `u64`; see `try_value`.
+    fn value(&self) -> u64 {
+        self.try_value().expect("overflow")
+    }
+
+    fn merge(&mut self, other: &GCounter<R>) {
+        self.counters.merge(&other.counters);
+    }
+}
+
+impl<R: ReplicaId> PartialEq for GCounter<R> {
+    fn eq(&self, other: &GCounter<R>) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
 }
 
-impl GCounter {
-    pub fn new() -> GCounter {
-        GCounter {
-            counters: HashMap::new(),
-        }
+impl<R: ReplicaId> Eq for GCounter<R> {}
+
+impl<R: ReplicaId> PartialOrd for GCounter<R> {
+    /// Orders counters by their per-replica counts, treating missing replicas
+    /// as zero.
+    fn partial_cmp(&self, other: &GCounter<R>) -> Option<Ordering> {
+        self.counters.partial_cmp(&other.counters)
+    }
+}
+
+/// An eventually consistent distributed counter that can be incremented and
+/// decremented, built from a pair of `GCounter`s.
+///
+/// Serializes as a struct with `inc` and `dec` fields, each holding a
+/// `GCounter`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PNCounter<R: ReplicaId> {
+    inc: GCounter<R>,
+    dec: GCounter<R>,
+}
+
+impl<R: ReplicaId> PNCounter<R> {
+    pub fn new() -> PNCounter<R> {
+        PNCounter {
+            inc: GCounter::new(),
+            dec: GCounter::new(),
+        }
+    }
+
+    /// Returns the counter's value, or `None` if it doesn't fit in an `i64`.
+    pub fn checked_value(&self) -> Option<i64> {
+        self.try_value().ok()
+    }
+
+    /// Returns the counter's value, or an error if it doesn't fit in an `i64`.
+    pub fn try_value(&self) -> Result<i64, OverflowError> {
+        // Both sums fit comfortably in an i128, so the difference can't wrap.
+        let value = self.inc.wide_value() as i128 - self.dec.wide_value() as i128;
+        value.try_into().map_err(|_| OverflowError)
+    }
+
+    /// Increments `replica`'s count, returning the operation to replicate.
+    pub fn inc(&mut self, replica: R, count: u64) -> PNCounterOp<R> {
+        let op = self.prepare(PNCounterOp::Inc { replica, count });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Decrements `replica`'s count, returning the operation to replicate.
+    pub fn dec(&mut self, replica: R, count: u64) -> PNCounterOp<R> {
+        let op = self.prepare(PNCounterOp::Dec { replica, count });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Increments `replica`'s count, or returns an error without modifying the
+    /// counter if the count would overflow a `u64`.
+    pub fn try_inc(&mut self, replica: R, count: u64) -> Result<PNCounterOp<R>, OverflowError> {
+        let GCounterOp { replica, count } = self.inc.try_inc(replica, count)?;
+        Ok(PNCounterOp::Inc { replica, count })
+    }
+
+    /// Decrements `replica`'s count, or returns an error without modifying the
+    /// counter if the count would overflow a `u64`.
+    pub fn try_dec(&mut self, replica: R, count: u64) -> Result<PNCounterOp<R>, OverflowError> {
+        let GCounterOp { replica, count } = self.dec.try_inc(replica, count)?;
+        Ok(PNCounterOp::Dec { replica, count })
+    }
+
+    /// Increments `replica`'s count, returning the corresponding delta-state.
+    pub fn inc_delta(&mut self, replica: R, count: u64) -> PNCounter<R> {
+        PNCounter {
+            inc: self.inc.inc_delta(replica, count),
+            dec: GCounter::new(),
+        }
+    }
+
+    /// Decrements `replica`'s count, returning the corresponding delta-state.
+    pub fn dec_delta(&mut self, replica: R, count: u64) -> PNCounter<R> {
+        PNCounter {
+            inc: GCounter::new(),
+            dec: self.dec.inc_delta(replica, count),
+        }
+    }
+}
+
+impl<R: ReplicaId> Default for PNCounter<R> {
+    fn default() -> PNCounter<R> {
+        PNCounter::new()
+    }
+}
+
+/// An operation on a `PNCounter`.
+///
+/// Serializes as an externally tagged enum: `Inc` or `Dec`, each wrapping a
+/// struct with `replica` and `count` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PNCounterOp<R> {
+    Inc { replica: R, count: u64 },
+    Dec { replica: R, count: u64 },
+}
+
+impl<R: ReplicaId> CmRDT for PNCounter<R> {
+    type Update = PNCounterOp<R>;
+    type Op = PNCounterOp<R>;
+
+    fn prepare(&self, update: PNCounterOp<R>) -> PNCounterOp<R> {
+        update
+    }
+
+    fn effect(&mut self, op: PNCounterOp<R>) {
+        match op {
+            PNCounterOp::Inc { replica, count } => {
+                self.inc.effect(GCounterOp { replica, count })
+            }
+            PNCounterOp::Dec { replica, count } => {
+                self.dec.effect(GCounterOp { replica, count })
+            }
+        }
+    }
+}
+
+impl<R: ReplicaId> CvRDT for PNCounter<R> {
+    type Value = i64;
+
+    /// # Panics
+    ///
+    /// Panics if the value doesn't fit in an `i64`; see `try_value`.
+    fn value(&self) -> i64 {
+        self.try_value().expect("overflow")
+    }
+
+    fn merge(&mut self, other: &PNCounter<R>) {
+        self.inc.merge(&other.inc);
+        self.dec.merge(&other.dec);
+    }
+}
+
+impl<R: ReplicaId> PartialOrd for PNCounter<R> {
+    fn partial_cmp(&self, other: &PNCounter<R>) -> Option<Ordering> {
+        combine_orderings(
+            self.inc.partial_cmp(&other.inc),
+            self.dec.partial_cmp(&other.dec),
+        )
+    }
+}
+
+/// The error returned when a replica tries to decrement a `BoundedCounter`, or
+/// transfer rights, beyond its local rights.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InsufficientRightsError {
+    pub requested: u64,
+    pub available: u64,
+}
+
+impl fmt::Display for InsufficientRightsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "insufficient rights: requested {}, available {}", self.requested, self.available)
+    }
+}
+
+impl Error for InsufficientRightsError {}
+
+/// A counter that never goes below zero, without coordination between
+/// replicas.
+///
+/// A `PNCounter` where every replica holds rights to part of the value: its
+/// own increments, plus the rights other replicas transferred to it, minus
+/// the rights it transferred away and its own decrements. A replica may only
+/// decrement or transfer within its rights, so concurrent decrements can
+/// never take the value below zero. Transfers are tracked per giver as a
+/// `GCounter` of the amounts given to each recipient.
+///
+/// Serializes as a struct with `counter` and `transfers` fields. `counter` holds
+/// a `PNCounter`, and `transfers` maps each giving replica ID to a `GCounter`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BoundedCounter<R: ReplicaId> {
+    counter: PNCounter<R>,
+    transfers: HashMap<R, GCounter<R>>,
+}
+
+impl<R: ReplicaId> BoundedCounter<R> {
+    pub fn new() -> BoundedCounter<R> {
+        BoundedCounter {
+            counter: PNCounter::new(),
+            transfers: HashMap::new(),
+        }
+    }
+
+    /// Returns the counter's value, or `None` if it overflows a `u64`.
+    pub fn checked_value(&self) -> Option<u64> {
+        self.try_value().ok()
+    }
+
+    /// Returns the counter's value, or an error if it overflows a `u64`.
+    pub fn try_value(&self) -> Result<u64, OverflowError> {
+        let value = self.counter.inc.wide_value().checked_sub(self.counter.dec.wide_value());
+        value.and_then(|value| value.try_into().ok()).ok_or(OverflowError)
+    }
+
+    /// Returns how much `replica` may decrement or transfer, as far as this
+    /// replica knows. The count is exact at `replica` itself.
+    pub fn rights(&self, replica: &R) -> u64 {
+        let received: u128 = self.transfers.values()
+            .map(|given| u128::from(given.counters.get(replica)))
+            .sum();
+        let given = self.transfers.get(replica).map_or(0, GCounter::wide_value);
+        let incs = self.counter.inc.counters.get(replica);
+        let decs = self.counter.dec.counters.get(replica);
+        let rights = (u128::from(incs) + received) as i128 - (given + u128::from(decs)) as i128;
+        rights.clamp(0, i128::from(u64::MAX)) as u64
+    }
+
+    /// Increments `replica`'s count, granting it as many rights, and returns
+    /// the operation to replicate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `replica`'s count overflows a `u64`.
+    pub fn inc(&mut self, replica: R, count: u64) -> BoundedCounterOp<R> {
+        let op = self.prepare(BoundedCounterOp::Inc { replica, count });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Decrements `replica`'s count, returning the operation to replicate, or
+    /// an error without modifying the counter if `replica` lacks the rights.
+    pub fn dec(
+        &mut self,
+        replica: R,
+        count: u64,
+    ) -> Result<BoundedCounterOp<R>, InsufficientRightsError> {
+        self.check_rights(&replica, count)?;
+        let op = self.prepare(BoundedCounterOp::Dec { replica, count });
+        self.effect(op.clone());
+        Ok(op)
+    }
+
+    /// Transfers `count` of `from`'s rights to `to`, returning the operation to
+    /// replicate, or an error without modifying the counter if `from` lacks
+    /// the rights.
+    pub fn transfer(
+        &mut self,
+        from: R,
+        to: R,
+        count: u64,
+    ) -> Result<BoundedCounterOp<R>, InsufficientRightsError> {
+        self.check_rights(&from, count)?;
+        let op = self.prepare(BoundedCounterOp::Transfer { from, to, count });
+        self.effect(op.clone());
+        Ok(op)
+    }
+
+    fn check_rights(&self, replica: &R, requested: u64) -> Result<(), InsufficientRightsError> {
+        let available = self.rights(replica);
+        if requested > available {
+            return Err(InsufficientRightsError { requested, available });
+        }
+        Ok(())
+    }
+}
+
+impl<R: ReplicaId> Default for BoundedCounter<R> {
+    fn default() -> BoundedCounter<R> {
+        BoundedCounter::new()
+    }
+}
+
+/// An operation on a `BoundedCounter`.
+///
+/// Serializes as an externally tagged enum: `Inc` and `Dec` wrap a struct with
+/// `replica` and `count` fields, and `Transfer` a struct with `from`, `to` and
+/// `count` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum BoundedCounterOp<R> {
+    Inc { replica: R, count: u64 },
+    Dec { replica: R, count: u64 },
+    Transfer { from: R, to: R, count: u64 },
+}
+
+impl<R: ReplicaId> CmRDT for BoundedCounter<R> {
+    type Update = BoundedCounterOp<R>;
+    type Op = BoundedCounterOp<R>;
+
+    /// # Panics
+    ///
+    /// Panics if the update decrements or transfers beyond the source
+    /// replica's rights; see `dec` and `transfer`.
+    fn prepare(&self, update: BoundedCounterOp<R>) -> BoundedCounterOp<R> {
+        match &update {
+            BoundedCounterOp::Inc { .. } => {}
+            BoundedCounterOp::Dec { replica, count }
+            | BoundedCounterOp::Transfer { from: replica, count, .. } => {
+                self.check_rights(replica, *count).expect("insufficient rights");
+            }
+        }
+        update
+    }
+
+    fn effect(&mut self, op: BoundedCounterOp<R>) {
+        match op {
+            BoundedCounterOp::Inc { replica, count } => {
+                self.counter.effect(PNCounterOp::Inc { replica, count })
+            }
+            BoundedCounterOp::Dec { replica, count } => {
+                self.counter.effect(PNCounterOp::Dec { replica, count })
+            }
+            BoundedCounterOp::Transfer { from, to, count } => {
+                self.transfers.entry(from).or_default().effect(GCounterOp { replica: to, count })
+            }
+        }
+    }
+}
+
+impl<R: ReplicaId> CvRDT for BoundedCounter<R> {
+    type Value = u64;
+
+    /// # Panics
+    ///
+    /// Panics if the value overflows a `u64`; see `try_value`.
+    fn value(&self) -> u64 {
+        self.try_value().expect("overflow")
+    }
+
+    fn merge(&mut self, other: &BoundedCounter<R>) {
+        self.counter.merge(&other.counter);
+        for (from, given) in other.transfers.iter() {
+            self.transfers.entry(from.clone()).or_default().merge(given);
+        }
+    }
+}
+
+impl<R: ReplicaId> PartialEq for BoundedCounter<R> {
+    fn eq(&self, other: &BoundedCounter<R>) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl<R: ReplicaId> Eq for BoundedCounter<R> {}
+
+impl<R: ReplicaId> PartialOrd for BoundedCounter<R> {
+    /// Orders counters by their `PNCounter`s and transfers, treating missing
+    /// givers as having given nothing.
+    fn partial_cmp(&self, other: &BoundedCounter<R>) -> Option<Ordering> {
+        let empty = GCounter::new();
+        let mut ordering = self.counter.partial_cmp(&other.counter);
+        for from in self.transfers.keys().chain(other.transfers.keys()) {
+            let local = self.transfers.get(from).unwrap_or(&empty);
+            let remote = other.transfers.get(from).unwrap_or(&empty);
+            ordering = combine_orderings(ordering, local.partial_cmp(remote));
+        }
+        ordering
+    }
+}
+
+/// A register holding a single value, where the write with the greatest
+/// timestamp wins. Writes with equal timestamps are ordered by replica ID.
+///
+/// Timestamps are typically taken from a `HybridLogicalClock`, so that a write
+/// always wins over the writes its replica has seen.
+/// Each replica must write every timestamp at most once: two values written
+/// by the same replica at the same timestamp tie, and replicas merging them
+/// in different orders keep different values.
+///
+/// Serializes as a struct with a single `entry` field, holding either `null`
+/// if the register was never written, or the winning `LWWRegisterOp`.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LWWRegister<T, R: ReplicaId> {
+    entry: Option<LWWRegisterOp<T, R>>,
+}
+
+impl<T, R: ReplicaId> LWWRegister<T, R> {
+    pub fn new() -> LWWRegister<T, R> {
+        LWWRegister { entry: None }
+    }
+
+    /// Returns the current value, or `None` if the register was never written.
+    pub fn get(&self) -> Option<&T> {
+        self.entry.as_ref().map(|entry| &entry.value)
+    }
+
+    /// Writes `value` at `timestamp`, returning the operation to replicate.
+    ///
+    /// The write is discarded if the register already holds a later one.
+    /// `replica` must never write two values at the same timestamp, which a
+    /// `HybridLogicalClock` per replica guarantees.
+    pub fn set(&mut self, value: T, timestamp: HlcTimestamp, replica: R) -> LWWRegisterOp<T, R>
+    where
+        T: Clone,
+    {
+        let op = self.prepare(LWWRegisterOp { timestamp, replica, value });
+        self.effect(op.clone());
+        op
+    }
+}
+
+impl<T, R: ReplicaId> Default for LWWRegister<T, R> {
+    fn default() -> LWWRegister<T, R> {
+        LWWRegister::new()
+    }
+}
+
+/// A write to an `LWWRegister`.
+///
+/// Serializes as a struct with `timestamp`, `replica` and `value` fields,
+/// where `timestamp` is an `HlcTimestamp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LWWRegisterOp<T, R> {
+    pub timestamp: HlcTimestamp,
+    pub replica: R,
+    pub value: T,
+}
+
+impl<T, R: Ord> LWWRegisterOp<T, R> {
+    /// Orders writes by timestamp, then by replica ID.
+    fn wins_over(&self, other: &LWWRegisterOp<T, R>) -> bool {
+        (self.timestamp, &self.replica) > (other.timestamp, &other.replica)
+    }
+}
+
+impl<T, R: ReplicaId> CmRDT for LWWRegister<T, R> {
+    type Update = LWWRegisterOp<T, R>;
+    type Op = LWWRegisterOp<T, R>;
+
+    fn prepare(&self, update: LWWRegisterOp<T, R>) -> LWWRegisterOp<T, R> {
+        update
+    }
+
+    fn effect(&mut self, op: LWWRegisterOp<T, R>) {
+        match &self.entry {
+            Some(entry) if !op.wins_over(entry) => {}
+            _ => self.entry = Some(op),
+        }
+    }
+}
+
+impl<T: Clone + PartialEq, R: ReplicaId> CvRDT for LWWRegister<T, R> {
+    type Value = Option<T>;
+
+    fn value(&self) -> Option<T> {
+        self.get().cloned()
+    }
+
+    fn merge(&mut self, other: &LWWRegister<T, R>) {
+        if let Some(entry) = &other.entry {
+            self.effect(entry.clone());
+        }
+    }
+}
+
+impl<T: PartialEq, R: ReplicaId> PartialEq for LWWRegister<T, R> {
+    fn eq(&self, other: &LWWRegister<T, R>) -> bool {
+        self.entry == other.entry
+    }
+}
+
+impl<T: Eq, R: ReplicaId> Eq for LWWRegister<T, R> {}
+
+impl<T: PartialEq, R: ReplicaId> PartialOrd for LWWRegister<T, R> {
+    /// Orders registers by their winning write. Two registers holding
+    /// different values under the same timestamp and replica are unordered.
+    fn partial_cmp(&self, other: &LWWRegister<T, R>) -> Option<Ordering> {
+        match (&self.entry, &other.entry) {
+            (None, None) => Some(Ordering::Equal),
+            (None, Some(_)) => Some(Ordering::Less),
+            (Some(_), None) => Some(Ordering::Greater),
+            (Some(a), Some(b)) => {
+                match (a.timestamp, &a.replica).cmp(&(b.timestamp, &b.replica)) {
+                    Ordering::Equal if a.value != b.value => None,
+                    ordering => Some(ordering),
+                }
+            }
+        }
+    }
+}
+
+/// A register that keeps every value written concurrently, rather than
+/// picking a winner.
+///
+/// Each value is tagged with the version vector of its write, which covers
+/// every write the writer had observed. A write supersedes exactly the values
+/// its version vector covers, so values written concurrently survive side by
+/// side until a later write observes them all.
+///
+/// Serializes as a struct with a single `values` field, holding a list of
+/// `[clock, value]` pairs where each clock maps replica IDs to counters.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MVRegister<T, R: ReplicaId> {
+    values: Vec<(VersionVector<R>, T)>,
+}
+
+impl<T, R: ReplicaId> MVRegister<T, R> {
+    pub fn new() -> MVRegister<T, R> {
+        MVRegister { values: vec![] }
+    }
+
+    /// Returns every value written concurrently, in no particular order.
+    pub fn read(&self) -> Vec<&T> {
+        self.values.iter().map(|(_, value)| value).collect()
+    }
+
+    /// Writes `value`, superseding every value currently held by this
+    /// replica, and returns the operation to replicate.
+    pub fn write(&mut self, value: T, replica: R) -> MVRegisterOp<T, R>
+    where
+        T: Clone,
+    {
+        let op = self.prepare((value, replica));
+        self.effect(op.clone());
+        op
+    }
+
+    /// Returns the join of the version vectors of all held values.
+    fn clock(&self) -> VersionVector<R> {
+        let mut clock = VersionVector::new();
+        for (value_clock, _) in self.values.iter() {
+            clock.merge(value_clock);
+        }
+        clock
+    }
+}
+
+impl<T, R: ReplicaId> Default for MVRegister<T, R> {
+    fn default() -> MVRegister<T, R> {
+        MVRegister::new()
+    }
+}
+
+/// A write to an `MVRegister`, tagged with the version vector of the write.
+///
+/// Serializes as a struct with `clock` and `value` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MVRegisterOp<T, R: ReplicaId> {
+    clock: VersionVector<R>,
+    pub value: T,
+}
+
+impl<T, R: ReplicaId> CmRDT for MVRegister<T, R> {
+    /// The value to write and the replica writing it.
+    type Update = (T, R);
+    type Op = MVRegisterOp<T, R>;
+
+    fn prepare(&self, update: (T, R)) -> MVRegisterOp<T, R> {
+        let (value, replica) = update;
+        let mut clock = self.clock();
+        clock.increment(replica);
+        MVRegisterOp { clock, value }
+    }
+
+    fn effect(&mut self, op: MVRegisterOp<T, R>) {
+        if self.values.iter().any(|(clock, _)| op.clock <= *clock) {
+            return;
+        }
+        self.values.retain(|(clock, _)| {
+            !matches!(clock.partial_cmp(&op.clock), Some(Ordering::Less) | Some(Ordering::Equal))
+        });
+        self.values.push((op.clock, op.value));
+    }
+}
+
+impl<T: Clone, R: ReplicaId> CvRDT for MVRegister<T, R> {
+    type Value = Vec<T>;
+
+    fn value(&self) -> Vec<T> {
+        self.read().into_iter().cloned().collect()
+    }
+
+    fn merge(&mut self, other: &MVRegister<T, R>) {
+        for (clock, value) in other.values.iter() {
+            self.effect(MVRegisterOp { clock: clock.clone(), value: value.clone() });
+        }
+    }
+}
+
+impl<T, R: ReplicaId> PartialEq for MVRegister<T, R> {
+    fn eq(&self, other: &MVRegister<T, R>) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl<T, R: ReplicaId> Eq for MVRegister<T, R> {}
+
+impl<T, R: ReplicaId> PartialOrd for MVRegister<T, R> {
+    /// Orders registers by the writes they have observed.
+    fn partial_cmp(&self, other: &MVRegister<T, R>) -> Option<Ordering> {
+        self.clock().partial_cmp(&other.clock())
+    }
+}
+
+/// A register that only grows: a write lower than the current value has no
+/// effect, and merge keeps the greater value.
+///
+/// Serializes as a struct with a single `value` field, holding `null` if the
+/// register was never written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MaxRegister<T: Ord> {
+    value: Option<T>,
+}
+
+impl<T: Ord> MaxRegister<T> {
+    pub fn new() -> MaxRegister<T> {
+        MaxRegister { value: None }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    /// Raises the register to `value`, returning the operation to replicate.
+    pub fn set(&mut self, value: T) -> T
+    where
+        T: Clone,
+    {
+        let op = self.prepare(value);
+        self.effect(op.clone());
+        op
+    }
+}
+
+impl<T: Ord> Default for MaxRegister<T> {
+    fn default() -> MaxRegister<T> {
+        MaxRegister::new()
+    }
+}
+
+impl<T: Ord> CmRDT for MaxRegister<T> {
+    /// The value to write.
+    type Update = T;
+    type Op = T;
+
+    fn prepare(&self, update: T) -> T {
+        update
+    }
+
+    fn effect(&mut self, op: T) {
+        if self.value.as_ref().is_none_or(|value| op > *value) {
+            self.value = Some(op);
+        }
+    }
+}
+
+impl<T: Ord + Clone> CvRDT for MaxRegister<T> {
+    type Value = Option<T>;
+
+    fn value(&self) -> Option<T> {
+        self.value.clone()
+    }
+
+    fn merge(&mut self, other: &MaxRegister<T>) {
+        if let Some(value) = &other.value {
+            self.effect(value.clone());
+        }
+    }
+}
+
+impl<T: Ord> PartialOrd for MaxRegister<T> {
+    /// Orders registers by value, with an unwritten register lowest.
+    fn partial_cmp(&self, other: &MaxRegister<T>) -> Option<Ordering> {
+        Some(self.value.cmp(&other.value))
+    }
+}
+
+/// A register that only shrinks: a write greater than the current value has
+/// no effect, and merge keeps the lesser value.
+///
+/// Serializes as a struct with a single `value` field, holding `null` if the
+/// register was never written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MinRegister<T: Ord> {
+    value: Option<T>,
+}
+
+impl<T: Ord> MinRegister<T> {
+    pub fn new() -> MinRegister<T> {
+        MinRegister { value: None }
+    }
+
+    pub fn get(&self) -> Option<&T> {
+        self.value.as_ref()
+    }
+
+    /// Lowers the register to `value`, returning the operation to replicate.
+    pub fn set(&mut self, value: T) -> T
+    where
+        T: Clone,
+    {
+        let op = self.prepare(value);
+        self.effect(op.clone());
+        op
+    }
+}
+
+impl<T: Ord> Default for MinRegister<T> {
+    fn default() -> MinRegister<T> {
+        MinRegister::new()
+    }
+}
+
+impl<T: Ord> CmRDT for MinRegister<T> {
+    /// The value to write.
+    type Update = T;
+    type Op = T;
+
+    fn prepare(&self, update: T) -> T {
+        update
+    }
+
+    fn effect(&mut self, op: T) {
+        if self.value.as_ref().is_none_or(|value| op < *value) {
+            self.value = Some(op);
+        }
+    }
+}
+
+impl<T: Ord + Clone> CvRDT for MinRegister<T> {
+    type Value = Option<T>;
+
+    fn value(&self) -> Option<T> {
+        self.value.clone()
+    }
+
+    fn merge(&mut self, other: &MinRegister<T>) {
+        if let Some(value) = &other.value {
+            self.effect(value.clone());
+        }
+    }
+}
+
+impl<T: Ord> PartialOrd for MinRegister<T> {
+    /// Orders registers by descending value, with an unwritten register
+    /// lowest.
+    fn partial_cmp(&self, other: &MinRegister<T>) -> Option<Ordering> {
+        match (&self.value, &other.value) {
+            (None, None) => Some(Ordering::Equal),
+            (None, Some(_)) => Some(Ordering::Less),
+            (Some(_), None) => Some(Ordering::Greater),
+            (Some(value), Some(other)) => Some(other.cmp(value)),
+        }
+    }
+}
+
+/// Orders `a` and `b` given whether `a <= b` and whether `b <= a`.
+fn inclusion_ordering(le: bool, ge: bool) -> Option<Ordering> {
+    match (le, ge) {
+        (true, true) => Some(Ordering::Equal),
+        (true, false) => Some(Ordering::Less),
+        (false, true) => Some(Ordering::Greater),
+        (false, false) => None,
+    }
+}
+
+/// Compares two sets by inclusion.
+fn subset_ordering<T: Hash + Eq>(a: &HashSet<T>, b: &HashSet<T>) -> Option<Ordering> {
+    inclusion_ordering(a.is_subset(b), b.is_subset(a))
+}
+
+/// A set that only grows: elements can be added but never removed.
+///
+/// Serializes as a struct with a single `elements` field holding a list of
+/// the set's elements.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct GSet<T: Hash + Eq> {
+    elements: HashSet<T>,
+}
+
+impl<T: Hash + Eq> GSet<T> {
+    pub fn new() -> GSet<T> {
+        GSet { elements: HashSet::new() }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.elements.contains(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.elements.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Adds `value` to the set, returning the operation to replicate.
+    pub fn insert(&mut self, value: T) -> T
+    where
+        T: Clone,
+    {
+        let op = self.prepare(value);
+        self.effect(op.clone());
+        op
+    }
+}
+
+impl<T: Hash + Eq> Default for GSet<T> {
+    fn default() -> GSet<T> {
+        GSet::new()
+    }
+}
+
+impl<T: Hash + Eq> CmRDT for GSet<T> {
+    /// The element to add.
+    type Update = T;
+    type Op = T;
+
+    fn prepare(&self, update: T) -> T {
+        update
+    }
+
+    fn effect(&mut self, op: T) {
+        self.elements.insert(op);
+    }
+}
+
+impl<T: Hash + Eq + Clone> CvRDT for GSet<T> {
+    type Value = HashSet<T>;
+
+    fn value(&self) -> HashSet<T> {
+        self.elements.clone()
+    }
+
+    fn merge(&mut self, other: &GSet<T>) {
+        self.elements.extend(other.elements.iter().cloned());
+    }
+}
+
+impl<T: Hash + Eq> PartialOrd for GSet<T> {
+    fn partial_cmp(&self, other: &GSet<T>) -> Option<Ordering> {
+        subset_ordering(&self.elements, &other.elements)
+    }
+}
+
+/// A set where an element can be added and then removed, but never added
+/// back, built from a pair of `GSet`s.
+///
+/// Serializes as a struct with `added` and `removed` fields, each holding a
+/// `GSet`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct TwoPSet<T: Hash + Eq> {
+    added: GSet<T>,
+    removed: GSet<T>,
+}
+
+impl<T: Hash + Eq> TwoPSet<T> {
+    pub fn new() -> TwoPSet<T> {
+        TwoPSet {
+            added: GSet::new(),
+            removed: GSet::new(),
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.added.contains(value) && !self.removed.contains(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.added.iter().filter(move |value| !self.removed.contains(value))
+    }
+
+    /// Adds `value` to the set, returning the operation to replicate.
+    ///
+    /// Adding an element that was removed before has no effect.
+    pub fn insert(&mut self, value: T) -> TwoPSetOp<T>
+    where
+        T: Clone,
+    {
+        let op = self.prepare(TwoPSetOp::Add(value));
+        self.effect(op.clone());
+        op
+    }
+
+    /// Removes `value` from the set, returning the operation to replicate, or
+    /// `None` if the set doesn't contain it.
+    pub fn remove(&mut self, value: T) -> Option<TwoPSetOp<T>>
+    where
+        T: Clone,
+    {
+        if !self.contains(&value) {
+            return None;
+        }
+        let op = self.prepare(TwoPSetOp::Remove(value));
+        self.effect(op.clone());
+        Some(op)
+    }
+}
+
+impl<T: Hash + Eq> Default for TwoPSet<T> {
+    fn default() -> TwoPSet<T> {
+        TwoPSet::new()
+    }
+}
+
+/// An operation on a `TwoPSet`.
+///
+/// Serializes as an externally tagged enum: `Add` or `Remove`, each wrapping
+/// the element.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TwoPSetOp<T> {
+    Add(T),
+    Remove(T),
+}
+
+impl<T: Hash + Eq> CmRDT for TwoPSet<T> {
+    type Update = TwoPSetOp<T>;
+    type Op = TwoPSetOp<T>;
+
+    fn prepare(&self, update: TwoPSetOp<T>) -> TwoPSetOp<T> {
+        update
+    }
+
+    fn effect(&mut self, op: TwoPSetOp<T>) {
+        match op {
+            TwoPSetOp::Add(value) => self.added.effect(value),
+            TwoPSetOp::Remove(value) => self.removed.effect(value),
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone> CvRDT for TwoPSet<T> {
+    type Value = HashSet<T>;
+
+    fn value(&self) -> HashSet<T> {
+        self.iter().cloned().collect()
+    }
+
+    fn merge(&mut self, other: &TwoPSet<T>) {
+        self.added.merge(&other.added);
+        self.removed.merge(&other.removed);
+    }
+}
+
+impl<T: Hash + Eq> PartialOrd for TwoPSet<T> {
+    fn partial_cmp(&self, other: &TwoPSet<T>) -> Option<Ordering> {
+        combine_orderings(
+            self.added.partial_cmp(&other.added),
+            self.removed.partial_cmp(&other.removed),
+        )
+    }
+}
+
+/// Identifies a single event: the `counter`-th event issued by `replica`.
+///
+/// Serializes as a struct with `replica` and `counter` fields.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Dot<R> {
+    pub replica: R,
+    pub counter: u64,
+}
+
+/// A set where an element can be removed and added back any number of times.
+/// An add concurrent with a remove of the same element wins.
+///
+/// Every add tags the element with a unique `Dot`, and a remove only deletes
+/// the tags it has observed, so a concurrent add's tag survives the remove.
+/// Removed tags are kept as tombstones, so the state grows with every add.
+///
+/// Serializes as a struct with `clock`, `elements` and `tombstones` fields.
+/// `clock` maps replica IDs to the number of adds issued by each replica,
+/// `elements` is a list of `[element, tags]` pairs listing the live tags of
+/// each element, and `tombstones` is a list of removed tags.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ORSet<T: Hash + Eq, R: ReplicaId> {
+    clock: VersionVector<R>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "map_as_pairs", bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))
+    )]
+    elements: HashMap<T, HashSet<Dot<R>>>,
+    tombstones: HashSet<Dot<R>>,
+}
+
+impl<T: Hash + Eq, R: ReplicaId> ORSet<T, R> {
+    pub fn new() -> ORSet<T, R> {
+        ORSet {
+            clock: VersionVector::new(),
+            elements: HashMap::new(),
+            tombstones: HashSet::new(),
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.elements.contains_key(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.elements.keys()
+    }
+
+    /// Adds `value` to the set, returning the operation to replicate.
+    pub fn add(&mut self, value: T, replica: R) -> ORSetOp<T, R>
+    where
+        T: Clone,
+    {
+        let op = self.prepare(ORSetUpdate::Add { value, replica });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Removes `value` from the set, returning the operation to replicate, or
+    /// `None` if the set doesn't contain it.
+    pub fn remove(&mut self, value: T) -> Option<ORSetOp<T, R>>
+    where
+        T: Clone,
+    {
+        if !self.contains(&value) {
+            return None;
+        }
+        let op = self.prepare(ORSetUpdate::Remove { value });
+        self.effect(op.clone());
+        Some(op)
+    }
+
+    /// Tags `value` with `dot`, unless the tag was already removed.
+    fn insert_dot(&mut self, value: T, dot: Dot<R>) {
+        self.clock.observe(&dot);
+        if !self.tombstones.contains(&dot) {
+            self.elements.entry(value).or_default().insert(dot);
+        }
+    }
+
+    /// Returns every tag this replica has seen, live or removed.
+    fn seen(&self) -> HashSet<&Dot<R>> {
+        self.elements.values().flatten().chain(self.tombstones.iter()).collect()
+    }
+}
+
+impl<T: Hash + Eq, R: ReplicaId> Default for ORSet<T, R> {
+    fn default() -> ORSet<T, R> {
+        ORSet::new()
+    }
+}
+
+/// An update to an `ORSet`, as requested at the source replica.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ORSetUpdate<T, R> {
+    Add { value: T, replica: R },
+    Remove { value: T },
+}
+
+/// An operation on an `ORSet`: add an element under a new tag, or remove the
+/// tags of an element observed at the source replica.
+///
+/// Serializes as an externally tagged enum: `Add` wraps a struct with `value`
+/// and `dot` fields, and `Remove` a struct with `value` and `dots` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ORSetOp<T, R> {
+    Add { value: T, dot: Dot<R> },
+    Remove { value: T, dots: Vec<Dot<R>> },
+}
+
+impl<T: Hash + Eq, R: ReplicaId> CmRDT for ORSet<T, R> {
+    type Update = ORSetUpdate<T, R>;
+    type Op = ORSetOp<T, R>;
+
+    fn prepare(&self, update: ORSetUpdate<T, R>) -> ORSetOp<T, R> {
+        match update {
+            ORSetUpdate::Add { value, replica } => {
+                let dot = self.clock.next_dot(replica);
+                ORSetOp::Add { value, dot }
+            }
+            ORSetUpdate::Remove { value } => {
+                let dots = self.elements.get(&value).into_iter().flatten().cloned().collect();
+                ORSetOp::Remove { value, dots }
+            }
+        }
+    }
+
+    fn effect(&mut self, op: ORSetOp<T, R>) {
+        match op {
+            ORSetOp::Add { value, dot } => self.insert_dot(value, dot),
+            ORSetOp::Remove { value, dots } => {
+                if let Some(live) = self.elements.get_mut(&value) {
+                    for dot in dots.iter() {
+                        live.remove(dot);
+                    }
+                    if live.is_empty() {
+                        self.elements.remove(&value);
+                    }
+                }
+                for dot in dots.into_iter() {
+                    self.clock.observe(&dot);
+                    self.tombstones.insert(dot);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone, R: ReplicaId> CvRDT for ORSet<T, R> {
+    type Value = HashSet<T>;
+
+    fn value(&self) -> HashSet<T> {
+        self.iter().cloned().collect()
+    }
+
+    fn merge(&mut self, other: &ORSet<T, R>) {
+        self.clock.merge(&other.clock);
+        self.tombstones.extend(other.tombstones.iter().cloned());
+        for live in self.elements.values_mut() {
+            live.retain(|dot| !other.tombstones.contains(dot));
+        }
+        self.elements.retain(|_, live| !live.is_empty());
+        for (value, live) in other.elements.iter() {
+            for dot in live.iter() {
+                self.insert_dot(value.clone(), dot.clone());
+            }
+        }
+    }
+}
+
+impl<T: Hash + Eq, R: ReplicaId> PartialEq for ORSet<T, R> {
+    fn eq(&self, other: &ORSet<T, R>) -> bool {
+        self.elements == other.elements && self.tombstones == other.tombstones
+    }
+}
+
+impl<T: Hash + Eq, R: ReplicaId> Eq for ORSet<T, R> {}
+
+impl<T: Hash + Eq, R: ReplicaId> PartialOrd for ORSet<T, R> {
+    /// Orders sets by the tags they have seen and the tags they have removed.
+    fn partial_cmp(&self, other: &ORSet<T, R>) -> Option<Ordering> {
+        combine_orderings(
+            subset_ordering(&self.seen(), &other.seen()),
+            subset_ordering(&self.tombstones, &other.tombstones),
+        )
+    }
+}
+
+/// The set of events a replica has seen: a version vector covering gapless
+/// prefixes of each replica's events, plus a cloud of dots seen out of order.
+///
+/// Dots are moved from the cloud into the version vector as soon as the gaps
+/// before them close, so the context stays as small as a plain version vector
+/// while every replica's dots are seen in order.
+///
+/// Serializes as a struct with `clock` and `cloud` fields. `clock` maps
+/// replica IDs to counters and `cloud` is a list of dots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CausalContext<R: ReplicaId> {
+    clock: VersionVector<R>,
+    cloud: HashSet<Dot<R>>,
+}
+
+impl<R: ReplicaId> CausalContext<R> {
+    pub fn new() -> CausalContext<R> {
+        CausalContext {
+            clock: VersionVector::new(),
+            cloud: HashSet::new(),
+        }
+    }
+
+    /// Returns the version vector of the gapless prefixes seen.
+    pub fn clock(&self) -> &VersionVector<R> {
+        &self.clock
+    }
+
+    /// Returns the dots seen out of order, in no particular order.
+    pub fn cloud(&self) -> impl Iterator<Item = &Dot<R>> {
+        self.cloud.iter()
+    }
+
+    pub fn contains(&self, dot: &Dot<R>) -> bool {
+        dot.counter <= self.clock.get(&dot.replica) || self.cloud.contains(dot)
+    }
+
+    /// Returns the dot of `replica`'s next event, without recording it.
+    pub fn next_dot(&self, replica: R) -> Dot<R> {
+        self.clock.next_dot(replica)
+    }
+
+    pub fn insert(&mut self, dot: Dot<R>) {
+        if !self.contains(&dot) {
+            self.cloud.insert(dot);
+            self.compact();
+        }
+    }
+
+    pub fn merge(&mut self, other: &CausalContext<R>) {
+        self.clock.merge(&other.clock);
+        self.cloud.extend(other.cloud.iter().cloned());
+        self.compact();
+    }
+
+    /// Returns whether every dot in `self` is also in `other`.
+    pub fn is_subset(&self, other: &CausalContext<R>) -> bool {
+        // A compacted context never holds the dot right after its version
+        // vector, so `other` covers our prefixes only if its vector does.
+        self.clock <= other.clock && self.cloud.iter().all(|dot| other.contains(dot))
+    }
+
+    /// Moves dots that extend the version vector's prefixes out of the cloud,
+    /// and drops dots the version vector already covers.
+    fn compact(&mut self) {
+        let mut dots: Vec<Dot<R>> = self.cloud.drain().collect();
+        dots.sort_unstable();
+        for dot in dots.into_iter() {
+            let seen = self.clock.get(&dot.replica);
+            if dot.counter == seen + 1 {
+                self.clock.observe(&dot);
+            } else if dot.counter > seen + 1 {
+                self.cloud.insert(dot);
+            }
+        }
+    }
+}
+
+impl<R: ReplicaId> Default for CausalContext<R> {
+    fn default() -> CausalContext<R> {
+        CausalContext::new()
+    }
+}
+
+/// The dots tagging a value of a causal CRDT, such as an element of an
+/// `ORSWOT` or a key of an `ORMap`.
+///
+/// A dot set is paired with the `CausalContext` of its replica: a dot that is
+/// in the context but not in the set was removed.
+///
+/// Serializes as a list of dots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct DotSet<R: ReplicaId> {
+    dots: HashSet<Dot<R>>,
+}
+
+impl<R: ReplicaId> DotSet<R> {
+    pub fn new() -> DotSet<R> {
+        DotSet { dots: HashSet::new() }
+    }
+
+    pub fn contains(&self, dot: &Dot<R>) -> bool {
+        self.dots.contains(dot)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Dot<R>> {
+        self.dots.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.dots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.dots.is_empty()
+    }
+
+    /// Adds `dot`, returning whether it was new.
+    pub fn insert(&mut self, dot: Dot<R>) -> bool {
+        self.dots.insert(dot)
+    }
+
+    /// Removes `dot`, returning whether it was present.
+    pub fn remove(&mut self, dot: &Dot<R>) -> bool {
+        self.dots.remove(dot)
+    }
+
+    /// Merges the dot set of another replica, given the causal contexts of
+    /// both replicas.
+    ///
+    /// Keeps the dots in both sets, and the dots in either set that the other
+    /// replica hasn't seen. A dot the other replica has seen but doesn't hold
+    /// was removed there.
+    pub fn merge(
+        &mut self,
+        context: &CausalContext<R>,
+        other: &DotSet<R>,
+        other_context: &CausalContext<R>,
+    ) {
+        self.dots.retain(|dot| other.contains(dot) || !other_context.contains(dot));
+        for dot in other.iter() {
+            if !context.contains(dot) {
+                self.dots.insert(dot.clone());
+            }
+        }
+    }
+
+    fn retain(&mut self, f: impl FnMut(&Dot<R>) -> bool) {
+        self.dots.retain(f);
+    }
+}
+
+impl<R: ReplicaId> Default for DotSet<R> {
+    fn default() -> DotSet<R> {
+        DotSet::new()
+    }
+}
+
+impl<R: ReplicaId> FromIterator<Dot<R>> for DotSet<R> {
+    fn from_iter<I: IntoIterator<Item = Dot<R>>>(iter: I) -> DotSet<R> {
+        DotSet { dots: iter.into_iter().collect() }
+    }
+}
+
+/// Returns whether merging a state with causal context `context` into one with
+/// causal context `other_context` would leave the latter unchanged, i.e. whether
+/// the former is `<=` the latter.
+///
+/// `other_dots` lists the other state's live dots along with their keys, and
+/// `is_live` tells whether a dot is live under a key in the former state. A
+/// live dot the former has seen but no longer holds was removed, and merging
+/// would remove it from the latter too.
+fn causal_le<'a, K, R: ReplicaId + 'a>(
+    context: &CausalContext<R>,
+    is_live: impl Fn(K, &Dot<R>) -> bool,
+    other_context: &CausalContext<R>,
+    mut other_dots: impl Iterator<Item = (K, &'a Dot<R>)>,
+) -> bool {
+    context.is_subset(other_context) && other_dots.all(|(key, dot)| !context.contains(dot) || is_live(key, dot))
+}
+
+/// An add-wins set that, unlike `ORSet`, keeps no tombstones.
+///
+/// Like `ORSet`, every add tags the element with a unique `Dot`. Instead of
+/// keeping removed tags around, the set tracks every dot it has seen in a
+/// causal context: a tag that is in the context but not in the set was removed.
+/// Removed elements take no space, and the context itself stays compact as
+/// long as each replica's dots are eventually seen without gaps.
+///
+/// Updates and operations are shared with `ORSet`. Operations must be
+/// delivered in causal order: an add after every operation its replica had
+/// seen, and a remove after the adds of the tags it removes. Merging states
+/// has no such requirement.
+///
+/// Serializes as a struct with `context` and `entries` fields. `context` holds
+/// a `clock` mapping replica IDs to counters and a `cloud` listing the dots seen
+/// out of order, and `entries` is a list of `[element, tags]` pairs listing the
+/// tags of each element.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ORSWOT<T: Hash + Eq, R: ReplicaId> {
+    context: CausalContext<R>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "map_as_pairs", bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))
+    )]
+    entries: HashMap<T, DotSet<R>>,
+}
+
+impl<T: Hash + Eq, R: ReplicaId> ORSWOT<T, R> {
+    pub fn new() -> ORSWOT<T, R> {
+        ORSWOT {
+            context: CausalContext::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.entries.contains_key(value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.keys()
+    }
+
+    /// Adds `value` to the set, returning the operation to replicate.
+    pub fn add(&mut self, value: T, replica: R) -> ORSetOp<T, R>
+    where
+        T: Clone,
+    {
+        let op = self.prepare(ORSetUpdate::Add { value, replica });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Removes `value` from the set, returning the operation to replicate, or
+    /// `None` if the set doesn't contain it.
+    pub fn remove(&mut self, value: T) -> Option<ORSetOp<T, R>>
+    where
+        T: Clone,
+    {
+        if !self.contains(&value) {
+            return None;
+        }
+        let op = self.prepare(ORSetUpdate::Remove { value });
+        self.effect(op.clone());
+        Some(op)
+    }
+
+    fn le(&self, other: &ORSWOT<T, R>) -> bool {
+        causal_le(
+            &self.context,
+            |value, dot| self.entries.get(value).is_some_and(|ours| ours.contains(dot)),
+            &other.context,
+            other.entries.iter().flat_map(|(value, dots)| dots.iter().map(move |dot| (value, dot))),
+        )
+    }
+}
+
+impl<T: Hash + Eq, R: ReplicaId> Default for ORSWOT<T, R> {
+    fn default() -> ORSWOT<T, R> {
+        ORSWOT::new()
+    }
+}
+
+impl<T: Hash + Eq, R: ReplicaId> CmRDT for ORSWOT<T, R> {
+    type Update = ORSetUpdate<T, R>;
+    type Op = ORSetOp<T, R>;
+
+    fn prepare(&self, update: ORSetUpdate<T, R>) -> ORSetOp<T, R> {
+        match update {
+            ORSetUpdate::Add { value, replica } => {
+                let dot = self.context.next_dot(replica);
+                ORSetOp::Add { value, dot }
+            }
+            ORSetUpdate::Remove { value } => {
+                let dots = self.entries.get(&value)
+                    .into_iter()
+                    .flat_map(DotSet::iter)
+                    .cloned()
+                    .collect();
+                ORSetOp::Remove { value, dots }
+            }
+        }
+    }
+
+    /// Applies `op`, which must be delivered in causal order: an add that
+    /// arrives after a remove of a later tag from the same replica would
+    /// revive the element.
+    fn effect(&mut self, op: ORSetOp<T, R>) {
+        match op {
+            ORSetOp::Add { value, dot } => {
+                if self.context.contains(&dot) {
+                    return;
+                }
+                let dots = self.entries.entry(value).or_default();
+                // The adding replica has observed its own earlier tags.
+                dots.retain(|old| old.replica != dot.replica);
+                dots.insert(dot.clone());
+                self.context.insert(dot);
+            }
+            ORSetOp::Remove { value, dots } => {
+                if let Some(live) = self.entries.get_mut(&value) {
+                    for dot in dots.iter() {
+                        live.remove(dot);
+                    }
+                    if live.is_empty() {
+                        self.entries.remove(&value);
+                    }
+                }
+                for dot in dots.into_iter() {
+                    self.context.insert(dot);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone, R: ReplicaId> CvRDT for ORSWOT<T, R> {
+    type Value = HashSet<T>;
+
+    fn value(&self) -> HashSet<T> {
+        self.iter().cloned().collect()
+    }
+
+    fn merge(&mut self, other: &ORSWOT<T, R>) {
+        let empty = DotSet::new();
+        for (value, dots) in self.entries.iter_mut() {
+            let theirs = other.entries.get(value).unwrap_or(&empty);
+            dots.merge(&self.context, theirs, &other.context);
+        }
+        for (value, theirs) in other.entries.iter() {
+            if !self.entries.contains_key(value) {
+                let mut dots = DotSet::new();
+                dots.merge(&self.context, theirs, &other.context);
+                self.entries.insert(value.clone(), dots);
+            }
+        }
+        self.entries.retain(|_, dots| !dots.is_empty());
+        self.context.merge(&other.context);
+    }
+}
+
+impl<T: Hash + Eq, R: ReplicaId> PartialOrd for ORSWOT<T, R> {
+    fn partial_cmp(&self, other: &ORSWOT<T, R>) -> Option<Ordering> {
+        inclusion_ordering(self.le(other), other.le(self))
+    }
+}
+
+/// The add and remove tags of an element in an `RWSet`.
+///
+/// Serializes as a struct with `adds` and `removes` fields, each holding a
+/// list of dots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Tokens<R: ReplicaId> {
+    adds: DotSet<R>,
+    removes: DotSet<R>,
+}
+
+impl<R: ReplicaId> Tokens<R> {
+    fn new() -> Tokens<R> {
+        Tokens {
+            adds: DotSet::new(),
+            removes: DotSet::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.adds.is_empty() && self.removes.is_empty()
+    }
+
+    fn contains(&self, dot: &Dot<R>) -> bool {
+        self.adds.contains(dot) || self.removes.contains(dot)
+    }
+
+    fn insert(&mut self, dot: Dot<R>, is_add: bool) {
+        if is_add {
+            self.adds.insert(dot);
+        } else {
+            self.removes.insert(dot);
+        }
+    }
+
+    fn remove(&mut self, dot: &Dot<R>) {
+        self.adds.remove(dot);
+        self.removes.remove(dot);
+    }
+
+    /// Returns every tag along with whether it tags an add.
+    fn iter(&self) -> impl Iterator<Item = (&Dot<R>, bool)> {
+        let adds = self.adds.iter().map(|dot| (dot, true));
+        adds.chain(self.removes.iter().map(|dot| (dot, false)))
+    }
+}
+
+/// A set where a remove concurrent with an add of the same element wins.
+///
+/// The remove-wins counterpart of `ORSWOT`: both adds and removes tag the
+/// element with a unique `Dot`, superseding the tags they observed. An element
+/// is in the set if it has add tags and no remove tags, so a remove's tag
+/// survives any add it didn't observe. Since removes are tagged too, `remove`
+/// takes the ID of the removing replica.
+///
+/// Operations must be delivered in causal order: an operation after every
+/// operation whose tags it observed. Merging states has no such requirement.
+///
+/// Serializes as a struct with `context` and `entries` fields. `context` holds
+/// a `clock` mapping replica IDs to counters and a `cloud` listing the dots seen
+/// out of order, and `entries` is a list of `[element, tokens]` pairs, where
+/// `tokens` is a struct with `adds` and `removes` fields, each holding a list of
+/// tags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RWSet<T: Hash + Eq, R: ReplicaId> {
+    context: CausalContext<R>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "map_as_pairs", bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))
+    )]
+    entries: HashMap<T, Tokens<R>>,
+}
+
+impl<T: Hash + Eq, R: ReplicaId> RWSet<T, R> {
+    pub fn new() -> RWSet<T, R> {
+        RWSet {
+            context: CausalContext::new(),
+            entries: HashMap::new(),
+        }
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        self.entries.get(value).is_some_and(|tokens| {
+            !tokens.adds.is_empty() && tokens.removes.is_empty()
+        })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.entries.keys().filter(move |value| self.contains(value))
+    }
+
+    /// Adds `value` to the set, returning the operation to replicate.
+    pub fn add(&mut self, value: T, replica: R) -> RWSetOp<T, R>
+    where
+        T: Clone,
+    {
+        let op = self.prepare(RWSetUpdate::Add { value, replica });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Removes `value` from the set, returning the operation to replicate, or
+    /// `None` if the set doesn't contain it.
+    pub fn remove(&mut self, value: T, replica: R) -> Option<RWSetOp<T, R>>
+    where
+        T: Clone,
+    {
+        if !self.contains(&value) {
+            return None;
+        }
+        let op = self.prepare(RWSetUpdate::Remove { value, replica });
+        self.effect(op.clone());
+        Some(op)
+    }
+
+    fn le(&self, other: &RWSet<T, R>) -> bool {
+        causal_le(
+            &self.context,
+            |value, dot| self.entries.get(value).is_some_and(|ours| ours.contains(dot)),
+            &other.context,
+            other.entries.iter().flat_map(|(value, tokens)| tokens.iter().map(move |(dot, _)| (value, dot))),
+        )
+    }
+}
+
+impl<T: Hash + Eq, R: ReplicaId> Default for RWSet<T, R> {
+    fn default() -> RWSet<T, R> {
+        RWSet::new()
+    }
+}
+
+/// An update to an `RWSet`, as requested at the source replica.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RWSetUpdate<T, R> {
+    Add { value: T, replica: R },
+    Remove { value: T, replica: R },
+}
+
+/// An operation on an `RWSet`: tag an element as added or removed with a new
+/// dot, superseding the tags observed at the source replica.
+///
+/// Serializes as an externally tagged enum: `Add` or `Remove`, each wrapping a
+/// struct with `value`, `dot` and `observed` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RWSetOp<T, R> {
+    Add { value: T, dot: Dot<R>, observed: Vec<Dot<R>> },
+    Remove { value: T, dot: Dot<R>, observed: Vec<Dot<R>> },
+}
+
+impl<T: Hash + Eq, R: ReplicaId> CmRDT for RWSet<T, R> {
+    type Update = RWSetUpdate<T, R>;
+    type Op = RWSetOp<T, R>;
+
+    fn prepare(&self, update: RWSetUpdate<T, R>) -> RWSetOp<T, R> {
+        let observed = |value: &T| -> Vec<Dot<R>> {
+            match self.entries.get(value) {
+                Some(tokens) => tokens.iter().map(|(dot, _)| dot.clone()).collect(),
+                None => vec![],
+            }
+        };
+        match update {
+            RWSetUpdate::Add { value, replica } => RWSetOp::Add {
+                dot: self.context.next_dot(replica),
+                observed: observed(&value),
+                value,
+            },
+            RWSetUpdate::Remove { value, replica } => RWSetOp::Remove {
+                dot: self.context.next_dot(replica),
+                observed: observed(&value),
+                value,
+            },
+        }
+    }
+
+    /// Applies `op`, which must be delivered in causal order: an operation
+    /// that arrives after one that observed its tag is ignored, so the tags it
+    /// superseded would survive.
+    fn effect(&mut self, op: RWSetOp<T, R>) {
+        let (value, dot, observed, is_add) = match op {
+            RWSetOp::Add { value, dot, observed } => (value, dot, observed, true),
+            RWSetOp::Remove { value, dot, observed } => (value, dot, observed, false),
+        };
+        if self.context.contains(&dot) {
+            return;
+        }
+        let tokens = self.entries.entry(value).or_insert_with(Tokens::new);
+        for old in observed.iter() {
+            tokens.remove(old);
+        }
+        tokens.insert(dot.clone(), is_add);
+        self.context.insert(dot);
+        for old in observed.into_iter() {
+            self.context.insert(old);
+        }
+    }
+}
+
+impl<T: Hash + Eq + Clone, R: ReplicaId> CvRDT for RWSet<T, R> {
+    type Value = HashSet<T>;
+
+    fn value(&self) -> HashSet<T> {
+        self.iter().cloned().collect()
+    }
+
+    fn merge(&mut self, other: &RWSet<T, R>) {
+        let empty = Tokens::new();
+        for value in other.entries.keys() {
+            self.entries.entry(value.clone()).or_insert_with(Tokens::new);
+        }
+        for (value, tokens) in self.entries.iter_mut() {
+            let theirs = other.entries.get(value).unwrap_or(&empty);
+            tokens.adds.merge(&self.context, &theirs.adds, &other.context);
+            tokens.removes.merge(&self.context, &theirs.removes, &other.context);
+        }
+        self.entries.retain(|_, tokens| !tokens.is_empty());
+        self.context.merge(&other.context);
+    }
+}
+
+impl<T: Hash + Eq, R: ReplicaId> PartialOrd for RWSet<T, R> {
+    fn partial_cmp(&self, other: &RWSet<T, R>) -> Option<Ordering> {
+        inclusion_ordering(self.le(other), other.le(self))
+    }
+}
+
+/// A boolean flag where an enable concurrent with a disable wins.
+///
+/// The flag is an `ORSWOT` of the unit value: it is enabled while the set holds
+/// `()`. Enabling adds `()` under a new tag, and disabling removes the tags it
+/// observed, so an enable the disable didn't observe survives it. Operations
+/// are shared with `ORSWOT`, and likewise must be delivered in causal order.
+///
+/// Serializes as an `ORSWOT<(), R>`: a struct with `context` and `entries`
+/// fields, where `entries` is empty or holds a single `[null, tags]` pair
+/// listing the enabling tags.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct EWFlag<R: ReplicaId> {
+    set: ORSWOT<(), R>,
+}
+
+impl<R: ReplicaId> EWFlag<R> {
+    /// Returns a disabled flag.
+    pub fn new() -> EWFlag<R> {
+        EWFlag { set: ORSWOT::new() }
+    }
+
+    pub fn read(&self) -> bool {
+        self.set.contains(&())
+    }
+
+    /// Enables the flag, returning the operation to replicate.
+    pub fn enable(&mut self, replica: R) -> ORSetOp<(), R> {
+        self.set.add((), replica)
+    }
+
+    /// Disables the flag, returning the operation to replicate, or `None` if
+    /// the flag is already disabled.
+    pub fn disable(&mut self) -> Option<ORSetOp<(), R>> {
+        self.set.remove(())
+    }
+}
+
+impl<R: ReplicaId> Default for EWFlag<R> {
+    fn default() -> EWFlag<R> {
+        EWFlag::new()
+    }
+}
+
+/// An update to an `EWFlag`, as requested at the source replica.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EWFlagUpdate<R> {
+    Enable { replica: R },
+    Disable,
+}
+
+impl<R: ReplicaId> CmRDT for EWFlag<R> {
+    type Update = EWFlagUpdate<R>;
+    type Op = ORSetOp<(), R>;
+
+    fn prepare(&self, update: EWFlagUpdate<R>) -> ORSetOp<(), R> {
+        match update {
+            EWFlagUpdate::Enable { replica } => self.set.prepare(ORSetUpdate::Add { value: (), replica }),
+            EWFlagUpdate::Disable => self.set.prepare(ORSetUpdate::Remove { value: () }),
+        }
+    }
+
+    fn effect(&mut self, op: ORSetOp<(), R>) {
+        self.set.effect(op);
+    }
+}
+
+impl<R: ReplicaId> CvRDT for EWFlag<R> {
+    type Value = bool;
+
+    fn value(&self) -> bool {
+        self.read()
+    }
+
+    fn merge(&mut self, other: &EWFlag<R>) {
+        self.set.merge(&other.set);
+    }
+}
+
+impl<R: ReplicaId> PartialOrd for EWFlag<R> {
+    fn partial_cmp(&self, other: &EWFlag<R>) -> Option<Ordering> {
+        self.set.partial_cmp(&other.set)
+    }
+}
+
+/// A boolean flag where a disable concurrent with an enable wins.
+///
+/// The mirror image of `EWFlag`: the flag is disabled while its `ORSWOT` holds
+/// `()`, so disabling adds `()` under a new tag and enabling removes the tags
+/// it observed.
+///
+/// Serializes like `EWFlag`, except that the tags are the disabling ones.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct DWFlag<R: ReplicaId> {
+    set: ORSWOT<(), R>,
+}
+
+impl<R: ReplicaId> DWFlag<R> {
+    /// Returns an enabled flag.
+    pub fn new() -> DWFlag<R> {
+        DWFlag { set: ORSWOT::new() }
+    }
+
+    pub fn read(&self) -> bool {
+        !self.set.contains(&())
+    }
+
+    /// Enables the flag, returning the operation to replicate, or `None` if
+    /// the flag is already enabled.
+    pub fn enable(&mut self) -> Option<ORSetOp<(), R>> {
+        self.set.remove(())
+    }
+
+    /// Disables the flag, returning the operation to replicate.
+    pub fn disable(&mut self, replica: R) -> ORSetOp<(), R> {
+        self.set.add((), replica)
+    }
+}
+
+impl<R: ReplicaId> Default for DWFlag<R> {
+    fn default() -> DWFlag<R> {
+        DWFlag::new()
+    }
+}
+
+/// An update to a `DWFlag`, as requested at the source replica.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DWFlagUpdate<R> {
+    Enable,
+    Disable { replica: R },
+}
+
+impl<R: ReplicaId> CmRDT for DWFlag<R> {
+    type Update = DWFlagUpdate<R>;
+    type Op = ORSetOp<(), R>;
+
+    fn prepare(&self, update: DWFlagUpdate<R>) -> ORSetOp<(), R> {
+        match update {
+            DWFlagUpdate::Enable => self.set.prepare(ORSetUpdate::Remove { value: () }),
+            DWFlagUpdate::Disable { replica } => self.set.prepare(ORSetUpdate::Add { value: (), replica }),
+        }
+    }
+
+    fn effect(&mut self, op: ORSetOp<(), R>) {
+        self.set.effect(op);
+    }
+}
+
+impl<R: ReplicaId> CvRDT for DWFlag<R> {
+    type Value = bool;
+
+    fn value(&self) -> bool {
+        self.read()
+    }
+
+    fn merge(&mut self, other: &DWFlag<R>) {
+        self.set.merge(&other.set);
+    }
+}
+
+impl<R: ReplicaId> PartialOrd for DWFlag<R> {
+    fn partial_cmp(&self, other: &DWFlag<R>) -> Option<Ordering> {
+        self.set.partial_cmp(&other.set)
+    }
+}
+
+/// Which operation wins when an element is added and removed with the same
+/// timestamp.
+///
+/// Serializes as a unit variant name, `"AddWins"` or `"RemoveWins"`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Bias {
+    #[default]
+    AddWins,
+    RemoveWins,
+}
+
+/// A set where each element's latest add or remove wins, by timestamp.
+///
+/// Only the latest add and remove timestamps of each element are kept, so the
+/// set carries no causal metadata; ties between an add and a remove are
+/// settled by the set's `Bias`. Replicas must share a bias: sets with
+/// different biases are incomparable, and merging or applying operations
+/// between them is a logic error. Timestamps are typically taken from a
+/// `HybridLogicalClock`, as for `LWWRegister`.
+///
+/// Serializes as a struct with `adds`, `removes` and `bias` fields. `adds` and
+/// `removes` are lists of `[element, timestamp]` pairs holding the latest add
+/// and remove timestamps of each element, where each `timestamp` is an
+/// `HlcTimestamp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LWWElementSet<T: Hash + Eq> {
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "map_as_pairs", bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))
+    )]
+    adds: HashMap<T, HlcTimestamp>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(with = "map_as_pairs", bound(serialize = "T: Serialize", deserialize = "T: Deserialize<'de>"))
+    )]
+    removes: HashMap<T, HlcTimestamp>,
+    bias: Bias,
+}
+
+impl<T: Hash + Eq> LWWElementSet<T> {
+    /// Creates an add-wins set.
+    pub fn new() -> LWWElementSet<T> {
+        LWWElementSet::with_bias(Bias::AddWins)
+    }
+
+    pub fn with_bias(bias: Bias) -> LWWElementSet<T> {
+        LWWElementSet {
+            adds: HashMap::new(),
+            removes: HashMap::new(),
+            bias,
+        }
+    }
+
+    pub fn bias(&self) -> Bias {
+        self.bias
+    }
+
+    pub fn contains(&self, value: &T) -> bool {
+        match (self.adds.get(value), self.removes.get(value)) {
+            (Some(added), Some(removed)) => match added.cmp(removed) {
+                Ordering::Greater => true,
+                Ordering::Equal => self.bias == Bias::AddWins,
+                Ordering::Less => false,
+            },
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.adds.keys().filter(move |value| self.contains(value))
+    }
+
+    /// Adds `value` at `timestamp`, returning the operation to replicate.
+    pub fn add(&mut self, value: T, timestamp: HlcTimestamp) -> LWWElementSetOp<T>
+    where
+        T: Clone,
+    {
+        let op = self.prepare(LWWElementSetOp::Add { value, timestamp });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Removes `value` at `timestamp`, returning the operation to replicate.
+    pub fn remove(&mut self, value: T, timestamp: HlcTimestamp) -> LWWElementSetOp<T>
+    where
+        T: Clone,
+    {
+        let op = self.prepare(LWWElementSetOp::Remove { value, timestamp });
+        self.effect(op.clone());
+        op
+    }
+}
+
+impl<T: Hash + Eq> Default for LWWElementSet<T> {
+    fn default() -> LWWElementSet<T> {
+        LWWElementSet::new()
+    }
+}
+
+/// An operation on an `LWWElementSet`.
+///
+/// Serializes as an externally tagged enum: `Add` or `Remove`, each wrapping a
+/// struct with `value` and `timestamp` fields, where `timestamp` is an
+/// `HlcTimestamp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum LWWElementSetOp<T> {
+    Add { value: T, timestamp: HlcTimestamp },
+    Remove { value: T, timestamp: HlcTimestamp },
+}
+
+impl<T: Hash + Eq> CmRDT for LWWElementSet<T> {
+    type Update = LWWElementSetOp<T>;
+    type Op = LWWElementSetOp<T>;
+
+    fn prepare(&self, update: LWWElementSetOp<T>) -> LWWElementSetOp<T> {
+        update
+    }
+
+    fn effect(&mut self, op: LWWElementSetOp<T>) {
+        let (timestamps, value, timestamp) = match op {
+            LWWElementSetOp::Add { value, timestamp } => (&mut self.adds, value, timestamp),
+            LWWElementSetOp::Remove { value, timestamp } => (&mut self.removes, value, timestamp),
+        };
+        let latest = timestamps.entry(value).or_insert(timestamp);
+        *latest = max(*latest, timestamp);
+    }
+}
+
+impl<T: Hash + Eq + Clone> CvRDT for LWWElementSet<T> {
+    type Value = HashSet<T>;
+
+    fn value(&self) -> HashSet<T> {
+        self.iter().cloned().collect()
+    }
+
+    fn merge(&mut self, other: &LWWElementSet<T>) {
+        merge_max(&mut self.adds, &other.adds);
+        merge_max(&mut self.removes, &other.removes);
+    }
+}
+
+impl<T: Hash + Eq> PartialOrd for LWWElementSet<T> {
+    /// Orders sets with the same bias by their add and remove timestamps,
+    /// element by element.
+    fn partial_cmp(&self, other: &LWWElementSet<T>) -> Option<Ordering> {
+        if self.bias != other.bias {
+            return None;
+        }
+        let mut ordering = Ordering::Equal;
+        for (ours, theirs) in [(&self.adds, &other.adds), (&self.removes, &other.removes)].iter() {
+            for k in ours.keys().chain(theirs.keys()) {
+                ordering = combine_orderings(Some(ordering), Some(ours.get(k).cmp(&theirs.get(k))))?;
+            }
+        }
+        Some(ordering)
+    }
+}
+
+/// A CRDT that can discard the updates observed by a remove, as the values of
+/// an `ORMap` do when their key is removed.
+pub trait ResetRemove {
+    /// Discards the updates `observed` holds, keeping those it hasn't seen.
+    fn reset_remove(&mut self, observed: &Self);
+}
+
+impl<R: ReplicaId> ResetRemove for GCounter<R> {
+    /// Subtracts `observed`'s count from each replica's count.
+    fn reset_remove(&mut self, observed: &GCounter<R>) {
+        self.counters.counters.retain(|replica, count| {
+            *count = count.saturating_sub(observed.counters.get(replica));
+            *count > 0
+        });
+    }
+}
+
+impl<R: ReplicaId> ResetRemove for PNCounter<R> {
+    fn reset_remove(&mut self, observed: &PNCounter<R>) {
+        self.inc.reset_remove(&observed.inc);
+        self.dec.reset_remove(&observed.dec);
+    }
+}
+
+impl<T, R: ReplicaId> ResetRemove for LWWRegister<T, R> {
+    /// Clears the register unless it holds a write later than `observed`'s.
+    fn reset_remove(&mut self, observed: &LWWRegister<T, R>) {
+        let observed = match (&self.entry, &observed.entry) {
+            (Some(entry), Some(seen)) => !entry.wins_over(seen),
+            _ => false,
+        };
+        if observed {
+            self.entry = None;
+        }
+    }
+}
+
+impl<T, R: ReplicaId> ResetRemove for MVRegister<T, R> {
+    /// Drops the values whose writes `observed` has seen.
+    fn reset_remove(&mut self, observed: &MVRegister<T, R>) {
+        let seen = observed.clock();
+        self.values.retain(|(clock, _)| {
+            !matches!(clock.partial_cmp(&seen), Some(Ordering::Less) | Some(Ordering::Equal))
+        });
+    }
+}
+
+/// A key's tags and value in an `ORMap`.
+///
+/// Serializes as a struct with `dots` and `value` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct MapEntry<V, R: ReplicaId> {
+    dots: DotSet<R>,
+    value: V,
+}
+
+/// A map from keys to nested CRDTs, such as per-user counters or registers.
+///
+/// Keys are tracked like the elements of an `ORSWOT`: every update tags its
+/// key with a unique `Dot`, and an update concurrent with a remove keeps the
+/// key in the map. Values of keys present on both sides of a merge are merged
+/// recursively.
+///
+/// Removing a key resets its value. The map remembers the value each remove
+/// observed, and `ResetRemove` discards it from the key's value whenever the
+/// value is read. A key updated concurrently with its remove comes back
+/// holding only the updates the remove didn't observe, and a key updated
+/// after its remove starts over from `V::default()`. These reset values are
+/// kept for as long as the map exists.
+///
+/// Operations must be delivered in causal order, as for `ORSWOT`. Merging
+/// states has no such requirement.
+///
+/// Serializes as a struct with `context`, `entries` and `resets` fields.
+/// `context` holds a `clock` mapping replica IDs to counters and a `cloud`
+/// listing the dots seen out of order, and `entries` is a list of
+/// `[key, entry]` pairs, where `entry` is a struct with a `dots` field listing
+/// the key's tags and a `value` field. `resets` is a list of `[key, value]`
+/// pairs holding the values observed by the removes of each key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ORMap<K: Hash + Eq, V, R: ReplicaId> {
+    context: CausalContext<R>,
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            with = "map_as_pairs",
+            bound(serialize = "K: Serialize, V: Serialize", deserialize = "K: Deserialize<'de>, V: Deserialize<'de>")
+        )
+    )]
+    entries: HashMap<K, MapEntry<V, R>>,
+    /// The join of the values observed by the removes of each key.
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            with = "map_as_pairs",
+            bound(serialize = "K: Serialize, V: Serialize", deserialize = "K: Deserialize<'de>, V: Deserialize<'de>")
+        )
+    )]
+    resets: HashMap<K, V>,
+}
+
+impl<K: Hash + Eq, V, R: ReplicaId> ORMap<K, V, R> {
+    pub fn new() -> ORMap<K, V, R> {
+        ORMap {
+            context: CausalContext::new(),
+            entries: HashMap::new(),
+            resets: HashMap::new(),
+        }
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.entries.contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the value at `key`, without the updates its removes observed,
+    /// or `None` if the map doesn't contain it.
+    pub fn get(&self, key: &K) -> Option<V>
+    where
+        V: ResetRemove + Clone,
+    {
+        self.entries.get(key).map(|entry| self.reset_value(key, entry))
+    }
+
+    /// Returns the keys along with their values, as returned by `get`, in no
+    /// particular order.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, V)>
+    where
+        V: ResetRemove + Clone,
+    {
+        self.entries.iter().map(move |(key, entry)| (key, self.reset_value(key, entry)))
+    }
+
+    /// Updates the value at `key` with the operation `f` returns, and returns
+    /// the operation to replicate.
+    ///
+    /// `f` prepares the operation on a copy of the key's value, including the
+    /// updates removes have reset, e.g.
+    /// `map.update(key, replica, |counter| counter.inc(replica, 1))`.
+    pub fn update<F>(&mut self, key: K, replica: R, f: F) -> ORMapOp<K, V, R>
+    where
+        K: Clone,
+        V: CmRDT + CvRDT + Clone + Default,
+        V::Op: Clone,
+        F: FnOnce(&mut V) -> V::Op,
+    {
+        let mut value = match self.entries.get(&key) {
+            Some(entry) => entry.value.clone(),
+            None => self.resets.get(&key).cloned().unwrap_or_default(),
+        };
+        let op = f(&mut value);
+        let op = self.prepare(ORMapUpdate::Update { key, replica, op });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Removes `key`, returning the operation to replicate, or `None` if the
+    /// map doesn't contain it.
+    pub fn remove(&mut self, key: K) -> Option<ORMapOp<K, V, R>>
+    where
+        K: Clone,
+        V: CmRDT + CvRDT + Clone + Default,
+        V::Op: Clone,
+    {
+        if !self.contains_key(&key) {
+            return None;
+        }
+        let op = self.prepare(ORMapUpdate::Remove { key });
+        self.effect(op.clone());
+        Some(op)
+    }
+
+    /// Returns `entry`'s value with the reset of `key` applied.
+    fn reset_value(&self, key: &K, entry: &MapEntry<V, R>) -> V
+    where
+        V: ResetRemove + Clone,
+    {
+        let mut value = entry.value.clone();
+        if let Some(observed) = self.resets.get(key) {
+            value.reset_remove(observed);
+        }
+        value
+    }
+}
+
+impl<K: Hash + Eq, V: PartialOrd, R: ReplicaId> ORMap<K, V, R> {
+    /// Like `causal_le`, also requiring the value of every key present on
+    /// both sides, and every reset, to be `<=` its counterpart in `other`.
+    fn le(&self, other: &ORMap<K, V, R>) -> bool {
+        causal_le(
+            &self.context,
+            |key, dot| self.entries.get(key).is_some_and(|ours| ours.dots.contains(dot)),
+            &other.context,
+            other.entries.iter().flat_map(|(key, theirs)| theirs.dots.iter().map(move |dot| (key, dot))),
+        ) && self.entries.iter().all(|(key, ours)| {
+                other.entries.get(key).is_none_or(|theirs| ours.value <= theirs.value)
+            })
+            && self.resets.iter().all(|(key, ours)| other.resets.get(key).is_some_and(|theirs| ours <= theirs))
+    }
+}
+
+impl<K: Hash + Eq, V, R: ReplicaId> Default for ORMap<K, V, R> {
+    fn default() -> ORMap<K, V, R> {
+        ORMap::new()
+    }
+}
+
+/// An update to an `ORMap`, as requested at the source replica.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ORMapUpdate<K, O, R> {
+    Update { key: K, replica: R, op: O },
+    Remove { key: K },
+}
+
+/// An operation on an `ORMap`: apply a nested operation to a key's value
+/// under a new tag, or remove the tags of a key observed at the source
+/// replica, along with the value they held.
+///
+/// Serializes as an externally tagged enum: `Update` wraps a struct with
+/// `key`, `dot` and `op` fields, and `Remove` a struct with `key`, `dots` and
+/// `value` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum ORMapOp<K, V: CmRDT, R> {
+    Update { key: K, dot: Dot<R>, op: V::Op },
+    Remove { key: K, dots: Vec<Dot<R>>, value: V },
+}
+
+impl<K: Hash + Eq + Clone, V: CmRDT + CvRDT + Clone + Default, R: ReplicaId> CmRDT for ORMap<K, V, R> {
+    type Update = ORMapUpdate<K, V::Op, R>;
+    type Op = ORMapOp<K, V, R>;
+
+    fn prepare(&self, update: ORMapUpdate<K, V::Op, R>) -> ORMapOp<K, V, R> {
+        match update {
+            ORMapUpdate::Update { key, replica, op } => {
+                let dot = self.context.next_dot(replica);
+                ORMapOp::Update { key, dot, op }
+            }
+            ORMapUpdate::Remove { key } => {
+                let (dots, value) = match self.entries.get(&key) {
+                    Some(entry) => (entry.dots.iter().cloned().collect(), entry.value.clone()),
+                    None => (vec![], V::default()),
+                };
+                ORMapOp::Remove { key, dots, value }
+            }
+        }
+    }
+
+    /// Applies `op`, which must be delivered in causal order: an update that
+    /// arrives after a remove that observed it would bring back the key.
+    fn effect(&mut self, op: ORMapOp<K, V, R>) {
+        match op {
+            ORMapOp::Update { key, dot, op } => {
+                if self.context.contains(&dot) {
+                    return;
+                }
+                let resets = &self.resets;
+                let entry = self.entries.entry(key).or_insert_with_key(|key| MapEntry {
+                    dots: DotSet::new(),
+                    value: resets.get(key).cloned().unwrap_or_default(),
+                });
+                // The updating replica has observed its own earlier tags.
+                entry.dots.retain(|old| old.replica != dot.replica);
+                entry.dots.insert(dot.clone());
+                entry.value.effect(op);
+                self.context.insert(dot);
+            }
+            ORMapOp::Remove { key, dots, value } => {
+                if let Some(entry) = self.entries.get_mut(&key) {
+                    for dot in dots.iter() {
+                        entry.dots.remove(dot);
+                    }
+                    if entry.dots.is_empty() {
+                        self.entries.remove(&key);
+                    }
+                }
+                self.resets.entry(key).or_default().merge(&value);
+                for dot in dots.into_iter() {
+                    self.context.insert(dot);
+                }
+            }
+        }
+    }
+}
+
+impl<K: Hash + Eq + Clone, V: CvRDT + ResetRemove + Clone, R: ReplicaId> CvRDT for ORMap<K, V, R> {
+    type Value = HashMap<K, V::Value>;
+
+    fn value(&self) -> HashMap<K, V::Value> {
+        self.iter().map(|(key, value)| (key.clone(), value.value())).collect()
+    }
+
+    fn merge(&mut self, other: &ORMap<K, V, R>) {
+        let context = &self.context;
+        let mut added = vec![];
+        for (key, theirs) in other.entries.iter() {
+            if self.entries.contains_key(key) {
+                continue;
+            }
+            let dots: DotSet<R> = theirs.dots.iter()
+                .filter(|dot| !context.contains(dot))
+                .cloned()
+                .collect();
+            if !dots.is_empty() {
+                added.push((key.clone(), MapEntry { dots, value: theirs.value.clone() }));
+            }
+        }
+
+        // Values always join: whatever a side's removed tags contributed is
+        // covered by the other side's resets.
+        let empty = DotSet::new();
+        self.entries.retain(|key, ours| {
+            match other.entries.get(key) {
+                Some(theirs) => {
+                    ours.dots.merge(context, &theirs.dots, &other.context);
+                    ours.value.merge(&theirs.value);
+                }
+                None => ours.dots.merge(context, &empty, &other.context),
+            }
+            !ours.dots.is_empty()
+        });
+
+        self.entries.extend(added);
+        for (key, theirs) in other.resets.iter() {
+            match self.resets.get_mut(key) {
+                Some(ours) => ours.merge(theirs),
+                None => {
+                    self.resets.insert(key.clone(), theirs.clone());
+                }
+            }
+        }
+        self.context.merge(&other.context);
+    }
+}
+
+impl<K: Hash + Eq, V: PartialOrd, R: ReplicaId> PartialOrd for ORMap<K, V, R> {
+    fn partial_cmp(&self, other: &ORMap<K, V, R>) -> Option<Ordering> {
+        inclusion_ordering(self.le(other), other.le(self))
+    }
+}
+
+/// A map where each key holds an `LWWRegister`, for keyed configuration that
+/// doesn't need nested CRDTs.
+///
+/// Removing a key writes a timestamped tombstone to its register, so a remove
+/// and a write race like any two writes: the later one wins. Timestamps are
+/// typically taken from a `HybridLogicalClock`, as for `LWWRegister`.
+///
+/// Serializes as a struct with a single `entries` field, a list of
+/// `[key, register]` pairs where `register` is an `LWWRegister` whose value is
+/// `null` for removed keys.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LWWMap<K: Hash + Eq, V, R: ReplicaId> {
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            with = "map_as_pairs",
+            bound(
+                serialize = "K: Serialize, V: Serialize, R: Serialize",
+                deserialize = "K: Deserialize<'de>, V: Deserialize<'de>, R: Deserialize<'de>"
+            )
+        )
+    )]
+    entries: HashMap<K, LWWRegister<Option<V>, R>>,
+}
+
+impl<K: Hash + Eq, V, R: ReplicaId> LWWMap<K, V, R> {
+    pub fn new() -> LWWMap<K, V, R> {
+        LWWMap { entries: HashMap::new() }
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).and_then(|register| register.get()?.as_ref())
+    }
+
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.get(key).is_some()
     }
 
-    pub fn value(&self) -> u64 {
-        self.counters.values().sum()
+    /// Returns the keys and values of the map, skipping removed keys.
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.entries.iter().filter_map(|(key, register)| Some((key, register.get()?.as_ref()?)))
     }
 
-    pub fn merge(&mut self, other: GCounter) {
-        let mut new_counts = vec![];
-        for (k, v_other) in other.counters.into_iter() {
-            if let Some(v_local) = self.counters.get_mut(&k) {
-                *v_local = max(*v_local, v_other);
-            } else {
-                new_counts.push((k, v_other));
-            }
-        }
+    /// Writes `value` at `key`, returning the operation to replicate.
+    pub fn insert(&mut self, key: K, value: V, timestamp: HlcTimestamp, replica: R) -> LWWMapOp<K, V, R>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let op = self.prepare(LWWMapOp {
+            key,
+            op: LWWRegisterOp { timestamp, replica, value: Some(value) },
+        });
+        self.effect(op.clone());
+        op
+    }
 
-        for (k, new_count) in new_counts.into_iter() {
-            self.counters.insert(k, new_count);
-        }
+    /// Removes `key` by writing a tombstone, returning the operation to
+    /// replicate.
+    pub fn remove(&mut self, key: K, timestamp: HlcTimestamp, replica: R) -> LWWMapOp<K, V, R>
+    where
+        K: Clone,
+        V: Clone,
+    {
+        let op = self.prepare(LWWMapOp {
+            key,
+            op: LWWRegisterOp { timestamp, replica, value: None },
+        });
+        self.effect(op.clone());
+        op
     }
+}
 
-    pub fn inc(&mut self, replica: String, count: u64) {
-        self.counters.entry(replica)
-            .and_modify(|v| { *v += count })
-            .or_insert(count);
+impl<K: Hash + Eq, V, R: ReplicaId> Default for LWWMap<K, V, R> {
+    fn default() -> LWWMap<K, V, R> {
+        LWWMap::new()
     }
 }
 
-#[derive(Debug)]
-pub struct PNCounter {
-    inc: GCounter,
-    dec: GCounter,
+/// A write to the register at `key` in an `LWWMap`, where a `None` value
+/// removes the key.
+///
+/// Serializes as a struct with `key` and `op` fields, where `op` is an
+/// `LWWRegisterOp`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct LWWMapOp<K, V, R> {
+    pub key: K,
+    pub op: LWWRegisterOp<Option<V>, R>,
 }
 
-impl PNCounter {
-    pub fn new() -> PNCounter {
-        PNCounter {
-            inc: GCounter::new(),
-            dec: GCounter::new(),
-        }
+impl<K: Hash + Eq, V, R: ReplicaId> CmRDT for LWWMap<K, V, R> {
+    type Update = LWWMapOp<K, V, R>;
+    type Op = LWWMapOp<K, V, R>;
+
+    fn prepare(&self, update: LWWMapOp<K, V, R>) -> LWWMapOp<K, V, R> {
+        update
     }
 
-    pub fn value(&self) -> i64 {
-        (self.inc.value() - self.dec.value()).try_into().expect("overflow")
+    fn effect(&mut self, op: LWWMapOp<K, V, R>) {
+        self.entries.entry(op.key).or_default().effect(op.op);
     }
+}
 
-    pub fn merge(&mut self, other: PNCounter) {
-        self.inc.merge(other.inc);
-        self.dec.merge(other.dec);
+impl<K: Hash + Eq + Clone, V: Clone + PartialEq, R: ReplicaId> CvRDT for LWWMap<K, V, R> {
+    type Value = HashMap<K, V>;
+
+    fn value(&self) -> HashMap<K, V> {
+        self.iter().map(|(key, value)| (key.clone(), value.clone())).collect()
     }
 
-    pub fn inc(&mut self, replica: String, count: u64) {
-        self.inc.inc(replica, count);
+    fn merge(&mut self, other: &LWWMap<K, V, R>) {
+        for (key, register) in other.entries.iter() {
+            self.entries.entry(key.clone()).or_default().merge(register);
+        }
     }
+}
 
-    pub fn dec(&mut self, replica: String, count: u64) {
-        self.dec.inc(replica, count);
+impl<K: Hash + Eq, V: PartialEq, R: ReplicaId> PartialOrd for LWWMap<K, V, R> {
+    /// Orders maps by their registers, key by key, treating missing keys as
+    /// registers that were never written.
+    fn partial_cmp(&self, other: &LWWMap<K, V, R>) -> Option<Ordering> {
+        let empty = LWWRegister::new();
+        let mut ordering = Ordering::Equal;
+        for key in self.entries.keys().chain(other.entries.keys()) {
+            let ours = self.entries.get(key).unwrap_or(&empty);
+            let theirs = other.entries.get(key).unwrap_or(&empty);
+            ordering = combine_orderings(Some(ordering), ours.partial_cmp(theirs))?;
+        }
+        Some(ordering)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use maplit::hashmap;
+    #[cfg(feature = "serde")]
+    use crate::test_util::assert_roundtrip;
+    use maplit::{hashmap, hashset};
+
+    /// Returns a timestamp at `physical` milliseconds.
+    fn ts(physical: u64) -> HlcTimestamp {
+        HlcTimestamp::new(physical, 0)
+    }
+
+    #[test]
+    fn test_version_vector() {
+        let mut a = VersionVector::new();
+        assert_eq!(a.increment('x'), 1);
+        let mut b = a.clone();
+        assert_eq!(a, b);
+        assert!(!a.dominates(&b) && !a.happened_before(&b));
+
+        b.increment('y');
+        assert!(b.dominates(&a));
+        assert!(a.happened_before(&b));
+
+        a.increment('x');
+        assert!(a.concurrent_with(&b));
+        assert!(!a.dominates(&b) && !b.dominates(&a));
+
+        a.merge(&b);
+        assert!(a.dominates(&b));
+        assert_eq!(a.get(&'x'), 2);
+        assert_eq!(a.get(&'y'), 1);
+        assert_eq!(a.get(&'z'), 0);
+        let mut entries: Vec<_> = a.iter().collect();
+        entries.sort_unstable();
+        assert_eq!(entries, vec![(&'x', 2), (&'y', 1)]);
+
+        // Missing replicas count as zero.
+        let mut c = VersionVector::new();
+        c.insert('z', 0);
+        assert_eq!(c, VersionVector::new());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_version_vector_serde() {
+        let mut clock = VersionVector::new();
+        clock.increment("a".to_string());
+        assert_eq!(serde_json::to_string(&clock).unwrap(), r#"{"a":1}"#);
+        assert_roundtrip(&clock);
+    }
 
     #[test]
     fn test_gcounter() {
@@ -89,14 +3016,23 @@ mod tests {
         counter_b.inc("a".to_string(), 10);
         counter_b.inc("b".to_string(), 21);
 
-        counter_a.merge(counter_b);
-        assert_eq!(counter_a.counters, hashmap!{
+        counter_a.merge(&counter_b);
+        assert_eq!(counter_a.counters.counters, hashmap!{
             "a".to_string() => 13,
             "b".to_string() => 21,
         });
         assert_eq!(counter_a.value(), 34);
     }
 
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_gcounter_serde() {
+        let mut counter = GCounter::new();
+        counter.inc("a".to_string(), 3);
+        assert_eq!(serde_json::to_string(&counter).unwrap(), r#"{"counters":{"a":3}}"#);
+        assert_roundtrip(&counter);
+    }
+
     #[test]
     fn test_pncounter() {
         let mut counter_a = PNCounter::new();
@@ -109,8 +3045,914 @@ mod tests {
         counter_b.inc("b".to_string(), 12);
         counter_b.dec("b".to_string(), 2);
 
-        counter_a.merge(counter_b);
+        counter_a.merge(&counter_b);
         println!("{:#?}", counter_a);
         assert_eq!(counter_a.value(), 18);
     }
+
+    #[test]
+    fn test_pncounter_ops() {
+        let mut counter_a = PNCounter::new();
+        let mut counter_b = PNCounter::new();
+
+        let ops = vec![
+            counter_a.inc("a".to_string(), 5),
+            counter_a.dec("a".to_string(), 2),
+        ];
+        let op_b = counter_b.inc("b".to_string(), 4);
+
+        counter_a.effect(op_b);
+        for op in ops.into_iter().rev() {
+            counter_b.effect(op);
+        }
+        assert_eq!(counter_a.value(), 7);
+        assert_eq!(counter_b.value(), 7);
+        assert_eq!(counter_a.partial_cmp(&counter_b), Some(Ordering::Equal));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_pncounter_serde() {
+        let mut counter: PNCounter<u64> = PNCounter::new();
+        let ops = vec![counter.inc(1, 5), counter.dec(2, 7)];
+        assert_eq!(serde_json::to_string(&ops[1]).unwrap(), r#"{"Dec":{"replica":2,"count":7}}"#);
+        assert_roundtrip(&counter);
+        assert_roundtrip(&ops);
+    }
+
+    #[test]
+    fn test_pncounter_deltas() {
+        let mut counter_a = PNCounter::new();
+        let mut counter_b = PNCounter::new();
+        counter_b.inc("b".to_string(), 1);
+
+        let mut deltas = DeltaGroup::new();
+        deltas.push(counter_a.inc_delta("a".to_string(), 5));
+        deltas.push(counter_a.inc_delta("a".to_string(), 2));
+        deltas.push(counter_a.dec_delta("a".to_string(), 3));
+
+        let group = deltas.take().unwrap();
+        assert!(deltas.is_empty());
+        assert_eq!(group.inc.counters.counters, hashmap!{ "a".to_string() => 7 });
+        assert_eq!(group.dec.counters.counters, hashmap!{ "a".to_string() => 3 });
+
+        counter_b.merge(&group);
+        assert_eq!(counter_b.value(), 5);
+        assert_eq!(counter_a.partial_cmp(&counter_b), Some(Ordering::Less));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_delta_group_serde() {
+        let mut counter = GCounter::new();
+        let mut deltas = DeltaGroup::new();
+        deltas.push(counter.inc_delta("b".to_string(), 1));
+        let json = serde_json::to_string(&deltas).unwrap();
+        assert_eq!(json, r#"{"counters":{"b":1}}"#);
+        let mut decoded: DeltaGroup<GCounter<String>> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.take(), deltas.take());
+    }
+
+    #[test]
+    fn test_pncounter_negative() {
+        let mut counter = PNCounter::new();
+        counter.inc("a".to_string(), 2);
+        counter.dec("b".to_string(), 5);
+        assert_eq!(counter.value(), -3);
+
+        counter.inc("a".to_string(), u64::MAX - 2);
+        counter.inc("b".to_string(), 10);
+        assert_eq!(counter.try_value(), Err(OverflowError));
+        assert_eq!(counter.checked_value(), None);
+
+        counter.dec("c".to_string(), u64::MAX);
+        assert_eq!(counter.value(), 10 - 5);
+    }
+
+    #[test]
+    fn test_gcounter_overflow() {
+        let mut counter = GCounter::new();
+        counter.inc("a".to_string(), u64::MAX);
+        assert_eq!(counter.try_inc("a".to_string(), 1), Err(OverflowError));
+        assert_eq!(counter.value(), u64::MAX);
+
+        counter.inc("b".to_string(), 1);
+        assert_eq!(counter.checked_value(), None);
+        assert_eq!(counter.try_value(), Err(OverflowError));
+    }
+
+    #[test]
+    fn test_integer_replica_ids() {
+        let mut counter_a: GCounter<u64> = GCounter::new();
+        counter_a.inc(1, 4);
+
+        let mut counter_b = GCounter::new();
+        counter_b.inc(2, 3);
+        counter_b.effect(GCounterOp { replica: 1, count: 1 });
+
+        counter_a.merge(&counter_b);
+        assert_eq!(counter_a.counters.counters, hashmap!{ 1 => 4, 2 => 3 });
+        assert_eq!(counter_a.value(), 7);
+    }
+
+    #[test]
+    fn test_join() {
+        let mut counter_a = GCounter::new();
+        counter_a.inc("a".to_string(), 2);
+
+        let mut counter_b = GCounter::new();
+        counter_b.inc("b".to_string(), 3);
+
+        let joined = counter_a.join(&counter_b);
+        assert_eq!(joined.value(), 5);
+        assert!(counter_a <= joined && counter_b <= joined);
+        assert!(counter_a.partial_cmp(&counter_b).is_none());
+
+        // Merging a subsumed state changes nothing.
+        let mut counter_c = joined.clone();
+        counter_c.merge(&counter_a);
+        assert_eq!(counter_c, joined);
+
+        // Zero counts are equivalent to missing entries.
+        counter_c.inc("c".to_string(), 0);
+        assert_eq!(counter_c, joined);
+    }
+
+    #[test]
+    fn test_compare() {
+        let mut counter_a = PNCounter::new();
+        counter_a.inc("a".to_string(), 3);
+
+        let mut counter_b = PNCounter::new();
+        counter_b.inc("a".to_string(), 3);
+        assert_eq!(counter_a.partial_cmp(&counter_b), Some(Ordering::Equal));
+
+        counter_b.dec("b".to_string(), 1);
+        assert_eq!(counter_a.partial_cmp(&counter_b), Some(Ordering::Less));
+        assert_eq!(counter_b.partial_cmp(&counter_a), Some(Ordering::Greater));
+
+        counter_a.inc("a".to_string(), 1);
+        assert_eq!(counter_a.partial_cmp(&counter_b), None);
+    }
+
+    #[test]
+    fn test_bounded_counter() {
+        let mut counter_a = BoundedCounter::new();
+        counter_a.inc('a', 10);
+        let mut counter_b = counter_a.clone();
+        assert_eq!(counter_b.rights(&'b'), 0);
+        assert_eq!(
+            counter_b.dec('b', 1),
+            Err(InsufficientRightsError { requested: 1, available: 0 })
+        );
+
+        // Only rights transferred to b can be spent there, so concurrent
+        // decrements can't take the value below zero.
+        let transfer = counter_a.transfer('a', 'b', 4).unwrap();
+        counter_b.effect(transfer);
+        assert_eq!(counter_a.rights(&'a'), 6);
+        assert_eq!(counter_b.rights(&'b'), 4);
+        counter_a.dec('a', 6).unwrap();
+        counter_b.dec('b', 4).unwrap();
+        assert!(counter_a.dec('a', 1).is_err());
+        assert!(counter_b.transfer('b', 'a', 1).is_err());
+        assert!(counter_a.partial_cmp(&counter_b).is_none());
+
+        counter_a.merge(&counter_b);
+        counter_b.merge(&counter_a);
+        assert_eq!(counter_a, counter_b);
+        assert_eq!(counter_a.value(), 0);
+
+        counter_b.inc('b', 3);
+        counter_b.transfer('b', 'a', 2).unwrap();
+        counter_a.merge(&counter_b);
+        assert_eq!(counter_a.value(), 3);
+        assert_eq!(counter_a.rights(&'a'), 2);
+        assert_eq!(counter_a.rights(&'b'), 1);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_bounded_counter_serde() {
+        let mut counter = BoundedCounter::new();
+        counter.inc("a".to_string(), 5);
+        let op = counter.transfer("a".to_string(), "b".to_string(), 2).unwrap();
+        assert_eq!(serde_json::to_string(&op).unwrap(), r#"{"Transfer":{"from":"a","to":"b","count":2}}"#);
+        assert_roundtrip(&counter);
+        assert_roundtrip(&op);
+    }
+
+    #[test]
+    fn test_lww_register() {
+        let mut register_a = LWWRegister::new();
+        register_a.set("x", ts(1), "a".to_string());
+        let op = register_a.set("y", ts(2), "a".to_string());
+        assert_eq!(register_a.get(), Some(&"y"));
+
+        // Equal timestamps are resolved by replica ID.
+        let mut register_b = LWWRegister::new();
+        register_b.set("z", ts(2), "b".to_string());
+        register_b.effect(op);
+        assert_eq!(register_b.get(), Some(&"z"));
+
+        // Stale writes are ignored.
+        register_a.set("old", ts(0), "c".to_string());
+        assert_eq!(register_a.get(), Some(&"y"));
+
+        assert!(register_a < register_b);
+        register_a.merge(&register_b);
+        assert_eq!(register_a, register_b);
+        assert_eq!(register_a.value(), Some("z"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_lww_register_serde() {
+        let mut register = LWWRegister::new();
+        register.set("x".to_string(), ts(4), 1u64);
+        let json = serde_json::to_string(&register).unwrap();
+        assert_eq!(json, r#"{"entry":{"timestamp":{"physical":4,"logical":0},"replica":1,"value":"x"}}"#);
+        assert_roundtrip(&register);
+    }
+
+    #[test]
+    fn test_lww_register_with_hlc() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        // Replica b's physical clock lags 50ms behind replica a's.
+        let time = Rc::new(Cell::new(1000));
+        let (time_a, time_b) = (time.clone(), time.clone());
+        let mut clock_a = HybridLogicalClock::with_source(move || time_a.get(), 100);
+        let mut clock_b = HybridLogicalClock::with_source(move || time_b.get() - 50, 100);
+
+        let mut register_a = LWWRegister::new();
+        let op = register_a.set("x", clock_a.now(), 'a');
+        let mut register_b = register_a.clone();
+        clock_b.update(op.timestamp).unwrap();
+
+        // A write made after observing another wins, despite the lagging
+        // physical clock.
+        register_b.set("y", clock_b.now(), 'b');
+        register_a.merge(&register_b);
+        assert_eq!(register_a.get(), Some(&"y"));
+        assert!(register_a.entry.as_ref().unwrap().timestamp > op.timestamp);
+    }
+
+    #[test]
+    fn test_mv_register() {
+        let mut register_a = MVRegister::new();
+        let mut register_b = MVRegister::new();
+        let op_a = register_a.write(1, "a".to_string());
+        let op_b = register_b.write(2, "b".to_string());
+
+        // Concurrent writes are both kept.
+        register_a.effect(op_b.clone());
+        register_b.merge(&register_a);
+        assert_eq!(register_a, register_b);
+        let mut values = register_a.value();
+        values.sort();
+        assert_eq!(values, vec![1, 2]);
+
+        // A write supersedes only the values it observed.
+        let mut register_c = MVRegister::new();
+        register_c.effect(op_a);
+        let op_c = register_c.write(3, "c".to_string());
+        register_a.effect(op_c);
+        let mut values = register_a.value();
+        values.sort();
+        assert_eq!(values, vec![2, 3]);
+
+        // Re-delivering an observed write changes nothing.
+        register_a.effect(op_b);
+        assert_eq!(register_a.read().len(), 2);
+
+        register_a.write(4, "a".to_string());
+        assert_eq!(register_a.read(), vec![&4]);
+        assert!(register_b < register_a);
+        register_b.merge(&register_a);
+        assert_eq!(register_b.read(), vec![&4]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_mv_register_serde() {
+        let mut register = MVRegister::new();
+        register.write("x".to_string(), 1u64);
+        assert_eq!(serde_json::to_string(&register).unwrap(), r#"{"values":[[{"1":1},"x"]]}"#);
+        assert_roundtrip(&register);
+    }
+
+    #[test]
+    fn test_max_min_registers() {
+        let mut max_a = MaxRegister::new();
+        let mut min_a = MinRegister::new();
+        assert_eq!(max_a.get(), None);
+        max_a.set(5);
+        min_a.set(5);
+        let mut max_b = max_a.clone();
+        let mut min_b = min_a.clone();
+        assert!(max_a > MaxRegister::new());
+        assert!(min_a > MinRegister::new());
+
+        max_a.set(3);
+        min_a.set(3);
+        assert_eq!(max_a.value(), Some(5));
+        assert_eq!(min_a.value(), Some(3));
+        assert!(min_a > min_b);
+
+        let op = max_b.set(8);
+        min_b.set(8);
+        max_a.effect(op);
+        min_a.merge(&min_b);
+        max_b.merge(&max_a);
+        assert_eq!(max_a, max_b);
+        assert_eq!(max_a.get(), Some(&8));
+        assert_eq!(min_a.get(), Some(&3));
+        assert!(min_b < min_a);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_max_min_registers_serde() {
+        let mut max = MaxRegister::new();
+        max.set(7u32);
+        assert_eq!(serde_json::to_string(&max).unwrap(), r#"{"value":7}"#);
+        assert_roundtrip(&max);
+        let mut min = MinRegister::new();
+        min.set("deadline".to_string());
+        assert_roundtrip(&min);
+    }
+
+    #[test]
+    fn test_gset() {
+        let mut set_a = GSet::new();
+        set_a.insert(1);
+        set_a.insert(2);
+
+        let mut set_b = GSet::new();
+        let op = set_b.insert(3);
+        assert!(set_a.partial_cmp(&set_b).is_none());
+
+        set_a.effect(op);
+        assert!(set_b < set_a);
+        set_b.merge(&set_a);
+        assert_eq!(set_b.value(), hashset!{1, 2, 3});
+        assert_eq!(set_a, set_b);
+    }
+
+    #[test]
+    fn test_two_p_set() {
+        let mut set_a = TwoPSet::new();
+        set_a.insert("x");
+        set_a.insert("y");
+        assert_eq!(set_a.remove("z"), None);
+
+        let mut set_b = set_a.clone();
+        assert_eq!(set_b.remove("x"), Some(TwoPSetOp::Remove("x")));
+        set_a.insert("z");
+
+        set_a.merge(&set_b);
+        assert_eq!(set_a.value(), hashset!{"y", "z"});
+
+        // Removed elements can't be added back.
+        set_a.insert("x");
+        assert!(!set_a.contains(&"x"));
+        assert_eq!(set_a.iter().count(), 2);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_two_p_set_serde() {
+        let mut set = TwoPSet::new();
+        set.insert(1);
+        set.remove(1);
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, r#"{"added":{"elements":[1]},"removed":{"elements":[1]}}"#);
+        assert_roundtrip(&set);
+    }
+
+    #[test]
+    fn test_or_set() {
+        let mut set_a = ORSet::new();
+        set_a.add("x", 'a');
+        let mut set_b = set_a.clone();
+
+        // A concurrent add wins over a remove.
+        let remove = set_a.remove("x").unwrap();
+        let add = set_b.add("x", 'b');
+        set_a.effect(add);
+        set_b.effect(remove);
+        assert!(set_a.contains(&"x"));
+        assert_eq!(set_a, set_b);
+
+        // Removed elements can be added back.
+        set_a.remove("x");
+        assert!(!set_a.contains(&"x"));
+        set_a.add("x", 'a');
+        set_a.add("y", 'a');
+        assert_eq!(set_a.value(), hashset!{"x", "y"});
+        assert_eq!(set_a.remove("z"), None);
+
+        set_b.add("z", 'b');
+        assert!(set_a.partial_cmp(&set_b).is_none());
+        set_b.merge(&set_a);
+        set_a.merge(&set_b);
+        assert_eq!(set_a, set_b);
+        assert_eq!(set_b.value(), hashset!{"x", "y", "z"});
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_or_set_serde() {
+        let mut set = ORSet::new();
+        set.add(1, "a".to_string());
+        let op = set.remove(1).unwrap();
+        set.add(2, "a".to_string());
+        let json = serde_json::to_string(&op).unwrap();
+        assert_eq!(json, r#"{"Remove":{"value":1,"dots":[{"replica":"a","counter":1}]}}"#);
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, r#"{"clock":{"a":2},"elements":[[2,[{"replica":"a","counter":2}]]],"tombstones":[{"replica":"a","counter":1}]}"#);
+        assert_roundtrip(&set);
+        assert_roundtrip(&op);
+
+        // Elements needn't serialize as strings to be JSON map keys.
+        let mut set = ORSet::new();
+        set.add((1u8, 2u8), 1u64);
+        assert_roundtrip(&set);
+    }
+
+    /// Checks that `context` holds exactly `seen`, in compact form: the
+    /// cloud only holds dots past a gap in their replica's prefix.
+    fn assert_compact(context: &CausalContext<char>, seen: &HashSet<Dot<char>>) {
+        for replica in ['a', 'b'].iter() {
+            let prefix = (1..).take_while(|&counter| {
+                seen.contains(&Dot { replica: *replica, counter })
+            }).count() as u64;
+            assert_eq!(context.clock().get(replica), prefix);
+            for counter in 1..=8 {
+                let dot = Dot { replica: *replica, counter };
+                assert_eq!(context.contains(&dot), seen.contains(&dot));
+            }
+        }
+        let cloud: HashSet<Dot<char>> = context.cloud().cloned().collect();
+        let expected = seen.iter()
+            .filter(|dot| dot.counter > context.clock().get(&dot.replica))
+            .cloned()
+            .collect();
+        assert_eq!(cloud, expected);
+    }
+
+    /// Returns every ordering of `dots`.
+    fn permutations(dots: &[Dot<char>]) -> Vec<Vec<Dot<char>>> {
+        if dots.is_empty() {
+            return vec![vec![]];
+        }
+        let mut orderings = vec![];
+        for i in 0..dots.len() {
+            let mut rest = dots.to_vec();
+            let first = rest.remove(i);
+            for mut ordering in permutations(&rest) {
+                ordering.insert(0, first.clone());
+                orderings.push(ordering);
+            }
+        }
+        orderings
+    }
+
+    #[test]
+    fn test_causal_context_insert_compaction() {
+        // Insert two replicas' dots in every order, so every gap closes at
+        // every possible point.
+        let dots: Vec<Dot<char>> = vec![('a', 1), ('a', 2), ('a', 3), ('a', 4), ('b', 1), ('b', 2)]
+            .into_iter()
+            .map(|(replica, counter)| Dot { replica, counter })
+            .collect();
+        for ordering in permutations(&dots) {
+            let mut context = CausalContext::new();
+            let mut seen = HashSet::new();
+            for dot in ordering.into_iter() {
+                context.insert(dot.clone());
+                // Inserting a dot twice changes nothing.
+                context.insert(dot.clone());
+                seen.insert(dot);
+                assert_compact(&context, &seen);
+            }
+            assert_eq!(context.cloud().count(), 0);
+        }
+    }
+
+    #[test]
+    fn test_causal_context_merge_compaction() {
+        // Split the dots between two contexts in every possible way, then
+        // merge them both ways.
+        let dots: Vec<Dot<char>> = (1..=4).map(|counter| Dot { replica: 'a', counter })
+            .chain((1..=3).map(|counter| Dot { replica: 'b', counter }))
+            .collect();
+        for mask in 0..(1 << dots.len()) {
+            let mut context_x = CausalContext::new();
+            let mut context_y = CausalContext::new();
+            let mut seen_x = HashSet::new();
+            let mut seen_y = HashSet::new();
+            for (i, dot) in dots.iter().enumerate() {
+                if mask & (1 << i) != 0 {
+                    context_x.insert(dot.clone());
+                    seen_x.insert(dot.clone());
+                } else {
+                    context_y.insert(dot.clone());
+                    seen_y.insert(dot.clone());
+                }
+            }
+            assert_compact(&context_x, &seen_x);
+            assert_compact(&context_y, &seen_y);
+            assert_eq!(context_x.is_subset(&context_y), seen_x.is_subset(&seen_y));
+
+            let mut merged = context_x.clone();
+            merged.merge(&context_y);
+            context_y.merge(&context_x);
+            assert_eq!(merged, context_y);
+            assert!(context_x.is_subset(&merged));
+            assert_eq!(merged.cloud().count(), 0);
+            assert_eq!(merged.clock().get(&'a'), 4);
+            assert_eq!(merged.clock().get(&'b'), 3);
+        }
+    }
+
+    #[test]
+    fn test_dot_set_merge() {
+        let dot = |replica, counter| Dot { replica, counter };
+        let mut context_x = CausalContext::new();
+        let mut context_y = CausalContext::new();
+        for counter in 1..=3 {
+            context_x.insert(dot('a', counter));
+        }
+        context_y.insert(dot('a', 1));
+        context_y.insert(dot('b', 1));
+
+        // x removed a1 and holds a2 and a3, which y hasn't seen; y still
+        // holds a1 and holds b1, which x hasn't seen.
+        let mut dots_x: DotSet<char> = vec![dot('a', 2), dot('a', 3)].into_iter().collect();
+        let dots_y: DotSet<char> = vec![dot('a', 1), dot('b', 1)].into_iter().collect();
+        dots_x.merge(&context_x, &dots_y, &context_y);
+        let expected: DotSet<char> = vec![dot('a', 2), dot('a', 3), dot('b', 1)].into_iter().collect();
+        assert_eq!(dots_x, expected);
+        assert_eq!(dots_x.len(), 3);
+        assert!(!dots_x.contains(&dot('a', 1)));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_causal_context_serde() {
+        let mut context = CausalContext::new();
+        context.insert(Dot { replica: 1u64, counter: 1 });
+        context.insert(Dot { replica: 1, counter: 3 });
+        let json = serde_json::to_string(&context).unwrap();
+        assert_eq!(json, r#"{"clock":{"1":1},"cloud":[{"replica":1,"counter":3}]}"#);
+        assert_roundtrip(&context);
+        let dots: DotSet<u64> = context.cloud().cloned().collect();
+        assert_roundtrip(&dots);
+    }
+
+    #[test]
+    fn test_orswot() {
+        let mut set_a = ORSWOT::new();
+        set_a.add("x", 'a');
+        let mut set_b = set_a.clone();
+
+        // A concurrent add wins over a remove.
+        set_a.remove("x");
+        set_b.add("x", 'b');
+        let mut merged = set_a.join(&set_b);
+        assert!(merged.contains(&"x"));
+        assert!(set_a < merged && set_b < merged);
+
+        // Removed elements take no space.
+        merged.remove("x");
+        assert!(merged.entries.is_empty());
+        merged.add("x", 'a');
+        merged.add("x", 'a');
+        assert_eq!(merged.entries[&"x"].len(), 1);
+
+        set_b.merge(&merged);
+        set_a.merge(&merged);
+        assert_eq!(set_a, set_b);
+        assert_eq!(set_a.value(), hashset!{"x"});
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_orswot_serde() {
+        let mut set = ORSWOT::new();
+        set.add(1, "a".to_string());
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, r#"{"context":{"clock":{"a":1},"cloud":[]},"entries":[[1,[{"replica":"a","counter":1}]]]}"#);
+        assert_roundtrip(&set);
+
+        let mut set = ORSWOT::new();
+        set.add((1u8, 2u8), 1u64);
+        assert_roundtrip(&set);
+    }
+
+    #[test]
+    fn test_rw_set() {
+        let mut set_a = RWSet::new();
+        set_a.add("x", 'a');
+        let mut set_b = set_a.clone();
+
+        // A concurrent remove wins over an add.
+        let remove = set_a.remove("x", 'a').unwrap();
+        let add = set_b.add("x", 'b');
+        set_a.effect(add);
+        set_b.effect(remove);
+        assert!(!set_a.contains(&"x"));
+        assert_eq!(set_a, set_b);
+
+        // An add that observed the remove brings the element back.
+        let add = set_b.add("x", 'b');
+        assert!(set_b.contains(&"x"));
+        set_a.effect(add);
+        assert_eq!(set_a.value(), hashset!{"x"});
+
+        set_a.remove("x", 'a');
+        set_b.add("y", 'b');
+        assert!(set_a.partial_cmp(&set_b).is_none());
+        set_b.merge(&set_a);
+        set_a.merge(&set_b);
+        assert_eq!(set_a, set_b);
+        assert_eq!(set_a.value(), hashset!{"y"});
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_rw_set_serde() {
+        let mut set = RWSet::new();
+        set.add(1, "a".to_string());
+        set.remove(1, "a".to_string());
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(
+            json,
+            r#"{"context":{"clock":{"a":2},"cloud":[]},"entries":[[1,{"adds":[],"removes":[{"replica":"a","counter":2}]}]]}"#
+        );
+        assert_roundtrip(&set);
+
+        let mut set = RWSet::new();
+        set.add((1u8, 2u8), 1u64);
+        assert_roundtrip(&set);
+    }
+
+    #[test]
+    fn test_add_wins_and_remove_wins() {
+        let mut add_wins_a = ORSWOT::new();
+        let mut remove_wins_a = RWSet::new();
+        add_wins_a.add("x", 'a');
+        remove_wins_a.add("x", 'a');
+        let mut add_wins_b = add_wins_a.clone();
+        let mut remove_wins_b = remove_wins_a.clone();
+
+        // Replica a removes "x" while replica b concurrently re-adds it.
+        add_wins_a.remove("x");
+        remove_wins_a.remove("x", 'a');
+        add_wins_b.add("x", 'b');
+        remove_wins_b.add("x", 'b');
+
+        add_wins_a.merge(&add_wins_b);
+        remove_wins_a.merge(&remove_wins_b);
+        assert!(add_wins_a.contains(&"x"));
+        assert!(!remove_wins_a.contains(&"x"));
+    }
+
+    #[test]
+    fn test_flags() {
+        let mut ew_a = EWFlag::new();
+        let mut dw_a = DWFlag::new();
+        assert!(!ew_a.read());
+        assert!(dw_a.read());
+        assert!(dw_a.enable().is_none());
+        ew_a.enable('a');
+        dw_a.disable('a');
+        let mut ew_b = ew_a.clone();
+        let mut dw_b = dw_a.clone();
+
+        // Replica a disables while replica b concurrently re-enables, and
+        // the other way around.
+        ew_a.disable().unwrap();
+        let enable = ew_b.enable('b');
+        ew_a.effect(enable);
+        assert!(ew_a.read());
+
+        let disable = dw_b.disable('b');
+        dw_a.enable().unwrap();
+        dw_a.effect(disable);
+        assert!(!dw_a.read());
+
+        // A toggle that observed the other wins.
+        let op = ew_b.prepare(EWFlagUpdate::Disable);
+        ew_b.effect(op);
+        assert!(ew_b > ew_a);
+        ew_a.merge(&ew_b);
+        assert_eq!(ew_a, ew_b);
+        assert!(!ew_a.value());
+
+        dw_b.merge(&dw_a);
+        dw_b.enable().unwrap();
+        dw_a.merge(&dw_b);
+        assert_eq!(dw_a, dw_b);
+        assert!(dw_a.value());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_flags_serde() {
+        let mut ew = EWFlag::new();
+        ew.enable(1u64);
+        let json = serde_json::to_string(&ew).unwrap();
+        assert_eq!(json, r#"{"context":{"clock":{"1":1},"cloud":[]},"entries":[[null,[{"replica":1,"counter":1}]]]}"#);
+        assert_roundtrip(&ew);
+        let mut dw = DWFlag::new();
+        dw.disable(1u64);
+        assert_roundtrip(&dw);
+    }
+
+    #[test]
+    fn test_lww_element_set() {
+        let mut add_wins = LWWElementSet::new();
+        let mut remove_wins = LWWElementSet::with_bias(Bias::RemoveWins);
+        for set in [&mut add_wins, &mut remove_wins].iter_mut() {
+            set.add("x", ts(1));
+            set.add("y", ts(1));
+            set.remove("x", ts(2));
+            set.add("x", ts(3));
+            set.remove("y", ts(1));
+        }
+        assert_eq!(add_wins.value(), hashset!{"x", "y"});
+        assert_eq!(remove_wins.value(), hashset!{"x"});
+
+        let mut other = LWWElementSet::with_bias(Bias::RemoveWins);
+        let op = other.remove("x", ts(4));
+        other.add("z", ts(2));
+        assert!(remove_wins.partial_cmp(&other).is_none());
+
+        remove_wins.effect(op);
+        remove_wins.merge(&other);
+        assert!(other < remove_wins);
+        assert_eq!(remove_wins.value(), hashset!{"z"});
+
+        // Sets with different biases are incomparable, even with the same
+        // timestamps.
+        let mut other = LWWElementSet::new();
+        other.merge(&remove_wins);
+        assert_ne!(other, remove_wins);
+        assert!(other.partial_cmp(&remove_wins).is_none());
+
+        // Stale timestamps don't overwrite newer ones.
+        remove_wins.add("x", ts(0));
+        assert!(!remove_wins.contains(&"x"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_lww_element_set_serde() {
+        let mut set = LWWElementSet::with_bias(Bias::RemoveWins);
+        set.add("x".to_string(), ts(1));
+        let json = serde_json::to_string(&set).unwrap();
+        assert_eq!(json, r#"{"adds":[["x",{"physical":1,"logical":0}]],"removes":[],"bias":"RemoveWins"}"#);
+        assert_roundtrip(&set);
+
+        let mut set = LWWElementSet::new();
+        set.add((1u8, 2u8), ts(1));
+        set.remove((3u8, 4u8), ts(2));
+        assert_roundtrip(&set);
+    }
+
+    #[test]
+    fn test_or_map() {
+        let mut map_a: ORMap<&str, GCounter<char>, char> = ORMap::new();
+        map_a.update("alice", 'a', |counter| counter.inc('a', 5));
+        map_a.update("bob", 'a', |counter| counter.inc('a', 1));
+        let mut map_b = map_a.clone();
+
+        // Values of keys updated on both sides are merged.
+        map_a.update("bob", 'a', |counter| counter.inc('a', 1));
+        map_b.update("bob", 'b', |counter| counter.inc('b', 3));
+        assert!(map_a.partial_cmp(&map_b).is_none());
+        map_a.merge(&map_b);
+        assert_eq!(map_a.get(&"bob").unwrap().value(), 5);
+
+        // A removed key doesn't come back from a state before the remove, and
+        // updating it again starts from scratch.
+        assert!(map_a.remove("alice").is_some());
+        assert!(map_a.remove("alice").is_none());
+        map_a.merge(&map_b);
+        assert!(!map_a.contains_key(&"alice"));
+        map_a.update("alice", 'a', |counter| counter.inc('a', 1));
+        map_a.merge(&map_b);
+        map_b.merge(&map_a);
+        assert_eq!(map_a, map_b);
+        assert_eq!(map_b.value(), hashmap!{ "alice" => 1, "bob" => 5 });
+
+        // An update concurrent with a remove keeps the key, holding only the
+        // updates the remove didn't observe.
+        map_a.remove("bob");
+        map_b.update("bob", 'b', |counter| counter.inc('b', 1));
+        map_a.merge(&map_b);
+        map_b.merge(&map_a);
+        assert_eq!(map_a, map_b);
+        assert_eq!(map_a.get(&"bob").unwrap().value(), 1);
+    }
+
+    #[test]
+    fn test_or_map_ops() {
+        let mut map_a: ORMap<&str, PNCounter<char>, char> = ORMap::new();
+        let mut map_b = ORMap::new();
+        map_b.effect(map_a.update("alice", 'a', |counter| counter.inc('a', 5)));
+
+        // A removes alice while B concurrently increments her counter.
+        let remove = map_a.remove("alice").unwrap();
+        let inc = map_b.update("alice", 'b', |counter| counter.inc('b', 1));
+        map_a.effect(inc);
+        map_b.effect(remove);
+        assert_eq!(map_a, map_b);
+        assert_eq!(map_a.get(&"alice").unwrap().value(), 1);
+
+        // Updates after the remove count from the reset value.
+        let dec = map_a.update("alice", 'a', |counter| counter.dec('a', 3));
+        map_b.effect(dec.clone());
+        map_b.effect(dec);
+        assert_eq!(map_a, map_b);
+        assert_eq!(map_b.value(), hashmap!{ "alice" => -2 });
+
+        // Registers are reset too.
+        let mut map: ORMap<u8, LWWRegister<&str, char>, char> = ORMap::new();
+        map.update(1, 'a', |register| register.set("x", ts(1), 'a'));
+        let mut other = map.clone();
+        map.remove(1);
+        other.update(1, 'b', |register| register.set("y", ts(2), 'b'));
+        map.merge(&other);
+        assert_eq!(map.get(&1).unwrap().get(), Some(&"y"));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_or_map_serde() {
+        let mut map: ORMap<String, PNCounter<u64>, u64> = ORMap::new();
+        let op = map.update("alice".to_string(), 1, |counter| counter.dec(1, 2));
+        let json = serde_json::to_string(&map).unwrap();
+        assert!(json.contains(r#""entries":[["alice",{"dots":[{"replica":1,"counter":1}],"value":"#));
+        assert_roundtrip(&map);
+        assert_roundtrip(&op);
+        assert_roundtrip(&map.remove("alice".to_string()).unwrap());
+        assert!(serde_json::to_string(&map).unwrap().contains(r#""resets":[["alice","#));
+        assert_roundtrip(&map);
+
+        let mut map: ORMap<(u8, u8), GCounter<u64>, u64> = ORMap::new();
+        map.update((1, 2), 1, |counter| counter.inc(1, 3));
+        map.remove((1, 2));
+        map.update((1, 2), 1, |counter| counter.inc(1, 1));
+        assert_roundtrip(&map);
+    }
+
+    #[test]
+    fn test_lww_map() {
+        let mut map_a = LWWMap::new();
+        map_a.insert("timeout", 30, ts(1), 'a');
+        map_a.insert("retries", 3, ts(1), 'a');
+        let mut map_b = map_a.clone();
+
+        let op = map_a.insert("timeout", 60, ts(2), 'a');
+        map_a.insert("retries", 5, ts(2), 'a');
+        map_b.remove("timeout", ts(3), 'b');
+        map_b.insert("timeout", 10, ts(2), 'b');
+        assert!(!map_b.contains_key(&"timeout"));
+        assert!(map_a.partial_cmp(&map_b).is_none());
+
+        // The later remove wins over the earlier write.
+        map_b.effect(op);
+        assert_eq!(map_b.get(&"timeout"), None);
+        map_a.merge(&map_b);
+        map_b.merge(&map_a);
+        assert_eq!(map_a, map_b);
+        assert_eq!(map_a.value(), hashmap!{ "retries" => 5 });
+
+        map_a.insert("timeout", 90, ts(4), 'a');
+        assert!(map_b < map_a);
+        map_b.merge(&map_a);
+        assert_eq!(map_b.iter().count(), 2);
+        assert_eq!(map_b.get(&"timeout"), Some(&90));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_lww_map_serde() {
+        let mut map = LWWMap::new();
+        map.insert("timeout".to_string(), 30, ts(1), 1u64);
+        map.remove("retries".to_string(), ts(1), 1);
+        let json = serde_json::to_string(&map).unwrap();
+        assert!(json.contains(r#"["timeout",{"entry":{"timestamp":{"physical":1,"logical":0},"replica":1,"value":30}}]"#));
+        assert!(json.contains(r#"["retries",{"entry":{"timestamp":{"physical":1,"logical":0},"replica":1,"value":null}}]"#));
+        assert_roundtrip(&map);
+
+        let mut map = LWWMap::new();
+        map.insert((1u8, 2u8), 30, ts(1), 1u64);
+        assert_roundtrip(&map);
+    }
 }
@@ -0,0 +1,367 @@
+//! A Replicated Growable Array: an ordered list CRDT.
+
+use std::cmp::{max, Ordering};
+use std::collections::HashSet;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{combine_orderings, subset_ordering, CmRDT, CvRDT, ReplicaId};
+
+/// Identifies an element of an `RGA` by the Lamport timestamp of its insert
+/// and the inserting replica. Elements are ordered by timestamp, then replica.
+///
+/// Serializes as a struct with `timestamp` and `replica` fields.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ElementId<R> {
+    pub timestamp: u64,
+    pub replica: R,
+}
+
+/// An element of an `RGA`, kept in list order even after it is deleted.
+///
+/// Serializes as a struct with `id`, `after`, `value` and `deleted` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Node<T, R> {
+    id: ElementId<R>,
+    /// The element this one was inserted after, or `None` for the head.
+    after: Option<ElementId<R>>,
+    value: T,
+    deleted: bool,
+}
+
+/// An ordered list supporting concurrent inserts and deletes.
+///
+/// Every element remembers the element it was inserted after. Elements
+/// inserted concurrently after the same element are ordered by descending
+/// `ElementId`, so every replica integrates them in the same order. Deleted
+/// elements stay in the list as tombstones, so later inserts can still refer
+/// to them.
+///
+/// Operations must be delivered in causal order: an insert after every insert
+/// it refers to, and a delete after the insert of the element it deletes.
+///
+/// Serializes as a struct with `nodes` and `timestamp` fields. `nodes` lists
+/// every element in order, tombstones included, and `timestamp` is the
+/// greatest Lamport timestamp seen by the replica.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RGA<T, R> {
+    nodes: Vec<Node<T, R>>,
+    timestamp: u64,
+}
+
+impl<T, R: ReplicaId> RGA<T, R> {
+    pub fn new() -> RGA<T, R> {
+        RGA {
+            nodes: vec![],
+            timestamp: 0,
+        }
+    }
+
+    /// Returns the number of elements, not counting deleted ones.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        self.iter().nth(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.nodes.iter().filter(|node| !node.deleted).map(|node| &node.value)
+    }
+
+    /// Inserts `value` at `index`, shifting later elements to the right, and
+    /// returns the operation to replicate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert(&mut self, index: usize, value: T, replica: R) -> RGAOp<T, R>
+    where
+        T: Clone,
+    {
+        let op = self.prepare(RGAUpdate::Insert { index, value, replica });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Deletes the element at `index`, returning the operation to replicate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index >= len`.
+    pub fn delete(&mut self, index: usize) -> RGAOp<T, R>
+    where
+        T: Clone,
+    {
+        let op = self.prepare(RGAUpdate::Delete { index });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Returns the position in `nodes` of the `index`-th visible element.
+    fn position(&self, index: usize) -> Option<usize> {
+        self.nodes.iter()
+            .enumerate()
+            .filter(|(_, node)| !node.deleted)
+            .map(|(i, _)| i)
+            .nth(index)
+    }
+
+    fn find(&self, id: &ElementId<R>) -> Option<usize> {
+        self.nodes.iter().position(|node| node.id == *id)
+    }
+
+    fn integrate(&mut self, id: ElementId<R>, after: Option<ElementId<R>>, value: T) {
+        if self.find(&id).is_some() {
+            return;
+        }
+        let mut i = match &after {
+            Some(after) => self.find(after).expect("insert after an unknown element") + 1,
+            None => 0,
+        };
+        // Skip the elements inserted concurrently after the same element with
+        // a greater ID, along with everything inserted after them.
+        while i < self.nodes.len() && self.nodes[i].id > id {
+            i += 1;
+        }
+        self.timestamp = max(self.timestamp, id.timestamp);
+        self.nodes.insert(i, Node { id, after, value, deleted: false });
+    }
+
+    fn ids(&self) -> HashSet<&ElementId<R>> {
+        self.nodes.iter().map(|node| &node.id).collect()
+    }
+
+    fn deleted(&self) -> HashSet<&ElementId<R>> {
+        self.nodes.iter().filter(|node| node.deleted).map(|node| &node.id).collect()
+    }
+}
+
+impl<T, R: ReplicaId> Default for RGA<T, R> {
+    fn default() -> RGA<T, R> {
+        RGA::new()
+    }
+}
+
+/// An update to an `RGA`, as requested at the source replica.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RGAUpdate<T, R> {
+    Insert { index: usize, value: T, replica: R },
+    Delete { index: usize },
+}
+
+/// An operation on an `RGA`: insert an element after another one, or delete
+/// an element.
+///
+/// Serializes as an externally tagged enum: `Insert` wraps a struct with `id`,
+/// `after` and `value` fields, and `Delete` a struct with an `id` field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum RGAOp<T, R> {
+    Insert { id: ElementId<R>, after: Option<ElementId<R>>, value: T },
+    Delete { id: ElementId<R> },
+}
+
+impl<T, R: ReplicaId> CmRDT for RGA<T, R> {
+    type Update = RGAUpdate<T, R>;
+    type Op = RGAOp<T, R>;
+
+    /// # Panics
+    ///
+    /// Panics if the update's index is out of bounds.
+    fn prepare(&self, update: RGAUpdate<T, R>) -> RGAOp<T, R> {
+        match update {
+            RGAUpdate::Insert { index, value, replica } => {
+                let after = match index {
+                    0 => None,
+                    _ => {
+                        let i = self.position(index - 1).expect("insert index out of bounds");
+                        Some(self.nodes[i].id.clone())
+                    }
+                };
+                let id = ElementId { timestamp: self.timestamp + 1, replica };
+                RGAOp::Insert { id, after, value }
+            }
+            RGAUpdate::Delete { index } => {
+                let i = self.position(index).expect("delete index out of bounds");
+                RGAOp::Delete { id: self.nodes[i].id.clone() }
+            }
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the operation refers to an element this replica hasn't seen,
+    /// i.e. if operations weren't delivered in causal order.
+    fn effect(&mut self, op: RGAOp<T, R>) {
+        match op {
+            RGAOp::Insert { id, after, value } => self.integrate(id, after, value),
+            RGAOp::Delete { id } => {
+                let i = self.find(&id).expect("delete of an unknown element");
+                self.nodes[i].deleted = true;
+            }
+        }
+    }
+}
+
+impl<T: Clone + PartialEq, R: ReplicaId> CvRDT for RGA<T, R> {
+    type Value = Vec<T>;
+
+    fn value(&self) -> Vec<T> {
+        self.iter().cloned().collect()
+    }
+
+    fn merge(&mut self, other: &RGA<T, R>) {
+        // Every element comes after the one it was inserted after, so walking
+        // the other list in order integrates elements after their anchors.
+        for node in other.nodes.iter() {
+            self.integrate(node.id.clone(), node.after.clone(), node.value.clone());
+            if node.deleted {
+                let i = self.find(&node.id).expect("merged element is missing");
+                self.nodes[i].deleted = true;
+            }
+        }
+    }
+}
+
+impl<T: PartialEq, R: ReplicaId> PartialOrd for RGA<T, R> {
+    /// Orders lists by the elements they have seen and the elements they
+    /// have deleted.
+    fn partial_cmp(&self, other: &RGA<T, R>) -> Option<Ordering> {
+        combine_orderings(
+            subset_ordering(&self.ids(), &other.ids()),
+            subset_ordering(&self.deleted(), &other.deleted()),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Rng;
+    #[cfg(feature = "serde")]
+    use crate::test_util::assert_roundtrip;
+
+    #[test]
+    fn test_rga() {
+        let mut list_a = RGA::new();
+        list_a.insert(0, 'a', 1);
+        list_a.insert(1, 'c', 1);
+        let mut list_b = list_a.clone();
+
+        // Concurrent inserts at the same position are ordered by ID.
+        let op_a = list_a.insert(1, 'b', 1);
+        let op_b = list_b.insert(1, 'x', 2);
+        let delete = list_b.delete(0);
+        list_a.effect(op_b);
+        list_a.effect(delete);
+        list_b.effect(op_a);
+        assert_eq!(list_a, list_b);
+        assert_eq!(list_a.value(), vec!['x', 'b', 'c']);
+        assert_eq!(list_a.get(1), Some(&'b'));
+        assert_eq!(list_a.len(), 3);
+
+        list_a.insert(3, 'd', 1);
+        list_b.delete(2);
+        assert!(list_a.partial_cmp(&list_b).is_none());
+        list_a.merge(&list_b);
+        list_b.merge(&list_a);
+        assert_eq!(list_a, list_b);
+        assert_eq!(list_b.value(), vec!['x', 'b', 'd']);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut list = RGA::new();
+        list.insert(0, 'a', 1u64);
+        let op = list.insert(0, 'b', 1);
+        list.delete(1);
+        let json = serde_json::to_string(&op).unwrap();
+        assert_eq!(json, r#"{"Insert":{"id":{"timestamp":2,"replica":1},"after":null,"value":"b"}}"#);
+        assert_roundtrip(&list);
+        assert_roundtrip(&op);
+    }
+
+    /// Applies a random edit to `list`, returning its operation.
+    fn random_edit(list: &mut RGA<u32, usize>, replica: usize, rng: &mut Rng) -> RGAOp<u32, usize> {
+        if list.is_empty() || rng.next(3) > 0 {
+            let index = rng.next(list.len() + 1);
+            list.insert(index, rng.next(1000) as u32, replica)
+        } else {
+            list.delete(rng.next(list.len()))
+        }
+    }
+
+    #[test]
+    fn test_random_ops_converge() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let mut lists = vec![RGA::new(); 3];
+        for _ in 0..20 {
+            // Each replica edits its list without hearing from the others.
+            let mut queues: Vec<Vec<RGAOp<u32, usize>>> = vec![];
+            for (replica, list) in lists.iter_mut().enumerate() {
+                let edits = rng.next(5);
+                queues.push((0..edits).map(|_| random_edit(list, replica, &mut rng)).collect());
+            }
+
+            // Each replica then receives the others' operations, interleaved
+            // at random but in order from each source.
+            for (replica, list) in lists.iter_mut().enumerate() {
+                let mut cursors = vec![0; queues.len()];
+                cursors[replica] = queues[replica].len();
+                loop {
+                    let pending: Vec<usize> = (0..queues.len())
+                        .filter(|&source| cursors[source] < queues[source].len())
+                        .collect();
+                    if pending.is_empty() {
+                        break;
+                    }
+                    let source = pending[rng.next(pending.len())];
+                    list.effect(queues[source][cursors[source]].clone());
+                    cursors[source] += 1;
+                }
+            }
+            assert_eq!(lists[0], lists[1]);
+            assert_eq!(lists[1], lists[2]);
+        }
+        assert!(!lists[0].is_empty());
+    }
+
+    #[test]
+    fn test_random_merges_converge() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        let mut lists = vec![RGA::new(); 4];
+        for _ in 0..100 {
+            let replica = rng.next(lists.len());
+            random_edit(&mut lists[replica], replica, &mut rng);
+
+            // Occasionally merge one replica's state into another.
+            if rng.next(4) == 0 {
+                let (from, to) = (rng.next(lists.len()), rng.next(lists.len()));
+                let state = lists[from].clone();
+                lists[to].merge(&state);
+                assert!(state <= lists[to]);
+            }
+        }
+
+        let mut merged = RGA::new();
+        for list in lists.iter() {
+            merged.merge(list);
+        }
+        for list in lists.iter_mut() {
+            list.merge(&merged);
+            assert_eq!(list.value(), merged.value());
+        }
+    }
+}
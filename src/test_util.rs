@@ -0,0 +1,28 @@
+//! Helpers shared by the tests of every module.
+
+#[cfg(feature = "serde")]
+use serde::{de::DeserializeOwned, Serialize};
+#[cfg(feature = "serde")]
+use std::fmt::Debug;
+
+/// Checks that `value` survives a round trip through JSON and bincode.
+#[cfg(feature = "serde")]
+pub(crate) fn assert_roundtrip<T: Serialize + DeserializeOwned + PartialEq + Debug>(value: &T) {
+    let json = serde_json::to_string(value).unwrap();
+    assert_eq!(&serde_json::from_str::<T>(&json).unwrap(), value);
+    let bytes = bincode::serialize(value).unwrap();
+    assert_eq!(&bincode::deserialize::<T>(&bytes).unwrap(), value);
+}
+
+/// A xorshift generator, to make random edits reproducible.
+pub(crate) struct Rng(pub(crate) u64);
+
+impl Rng {
+    /// Returns a number below `bound`.
+    pub(crate) fn next(&mut self, bound: usize) -> usize {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 % bound as u64) as usize
+    }
+}
@@ -0,0 +1,623 @@
+//! A collaborative plain-text CRDT.
+
+use std::cmp::{max, Ordering};
+use std::collections::HashSet;
+use std::fmt;
+use std::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use super::{combine_orderings, subset_ordering, CmRDT, CvRDT, ElementId, ReplicaId};
+
+/// A run of characters with consecutive IDs, each inserted after the one
+/// before it, and all deleted or all present.
+///
+/// Serializes as a struct with `id`, `after`, `text` and `deleted` fields.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+struct Run<R> {
+    /// The ID of the first character. The ID of the `i`-th character has the
+    /// same replica and a timestamp greater by `i`.
+    id: ElementId<R>,
+    /// The character the first character was inserted after, or `None` for
+    /// the start of the text.
+    after: Option<ElementId<R>>,
+    text: String,
+    deleted: bool,
+}
+
+impl<R: ReplicaId> Run<R> {
+    fn len(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    fn char_id(&self, offset: usize) -> ElementId<R> {
+        ElementId {
+            timestamp: self.id.timestamp + offset as u64,
+            replica: self.id.replica.clone(),
+        }
+    }
+
+    /// Returns the offset of the character `id` in this run.
+    fn offset_of(&self, id: &ElementId<R>) -> Option<usize> {
+        if id.replica != self.id.replica || id.timestamp < self.id.timestamp {
+            return None;
+        }
+        let offset = (id.timestamp - self.id.timestamp) as usize;
+        if offset < self.len() {
+            Some(offset)
+        } else {
+            None
+        }
+    }
+
+    /// Splits the run before the character at `offset`, returning the tail.
+    fn split_off(&mut self, offset: usize) -> Run<R> {
+        let (byte, _) = self.text.char_indices().nth(offset).expect("split past the end of a run");
+        Run {
+            id: self.char_id(offset),
+            after: Some(self.char_id(offset - 1)),
+            text: self.text.split_off(byte),
+            deleted: self.deleted,
+        }
+    }
+
+    /// Returns whether `next` continues this run.
+    fn continued_by(&self, next: &Run<R>) -> bool {
+        let len = self.len();
+        self.deleted == next.deleted
+            && next.id == self.char_id(len)
+            && next.after.as_ref() == Some(&self.char_id(len - 1))
+    }
+}
+
+/// A range of consecutive character IDs, as deleted by a `TextOp`.
+///
+/// Serializes as a struct with `id` and `len` fields.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct CharSpan<R> {
+    /// The ID of the first character.
+    pub id: ElementId<R>,
+    pub len: usize,
+}
+
+/// A plain-text document supporting concurrent edits.
+///
+/// Characters are ordered like the elements of an `RGA`, but consecutive
+/// characters typed together are stored as a single run rather than one node
+/// per character. Runs are split when an edit lands in their middle, and
+/// joined back together when they line up again.
+///
+/// Indexes count Unicode scalar values (`char`s). Use `char_to_utf8`,
+/// `char_to_utf16` and friends to convert from and to the byte offsets and
+/// UTF-16 code unit offsets used by editors.
+///
+/// Operations must be delivered in causal order, as for an `RGA`.
+///
+/// Serializes as a struct with `runs` and `timestamp` fields. `runs` lists
+/// every run in order, deleted ones included, each as a struct with `id`,
+/// `after`, `text` and `deleted` fields, and `timestamp` is the greatest
+/// Lamport timestamp seen by the replica.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Text<R> {
+    runs: Vec<Run<R>>,
+    timestamp: u64,
+}
+
+impl<R: ReplicaId> Text<R> {
+    pub fn new() -> Text<R> {
+        Text {
+            runs: vec![],
+            timestamp: 0,
+        }
+    }
+
+    /// Returns the length of the text in `char`s.
+    pub fn len(&self) -> usize {
+        self.visible_runs().map(Run::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.visible_runs().all(|run| run.text.is_empty())
+    }
+
+    pub fn chars(&self) -> impl Iterator<Item = char> + '_ {
+        self.visible_runs().flat_map(|run| run.text.chars())
+    }
+
+    /// Inserts `text` before the `char` at `index`, returning the operation
+    /// to replicate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`.
+    pub fn insert_str(&mut self, index: usize, text: &str, replica: R) -> TextOp<R> {
+        let op = self.prepare(TextUpdate::Insert { index, text: text.to_string(), replica });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Deletes the `char`s in `range`, returning the operation to replicate.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds.
+    pub fn delete_range(&mut self, range: Range<usize>) -> TextOp<R> {
+        let op = self.prepare(TextUpdate::Delete { range });
+        self.effect(op.clone());
+        op
+    }
+
+    /// Converts a `char` index to a UTF-8 byte offset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `index > len`. The other conversions also panic if the index
+    /// falls inside a character.
+    pub fn char_to_utf8(&self, index: usize) -> usize {
+        self.convert_index(index, |_| 1, char::len_utf8)
+    }
+
+    /// Converts a UTF-8 byte offset to a `char` index.
+    pub fn utf8_to_char(&self, index: usize) -> usize {
+        self.convert_index(index, char::len_utf8, |_| 1)
+    }
+
+    /// Converts a `char` index to a UTF-16 code unit offset.
+    pub fn char_to_utf16(&self, index: usize) -> usize {
+        self.convert_index(index, |_| 1, char::len_utf16)
+    }
+
+    /// Converts a UTF-16 code unit offset to a `char` index.
+    pub fn utf16_to_char(&self, index: usize) -> usize {
+        self.convert_index(index, char::len_utf16, |_| 1)
+    }
+
+    /// Converts a UTF-8 byte offset to a UTF-16 code unit offset.
+    pub fn utf8_to_utf16(&self, index: usize) -> usize {
+        self.convert_index(index, char::len_utf8, char::len_utf16)
+    }
+
+    /// Converts a UTF-16 code unit offset to a UTF-8 byte offset.
+    pub fn utf16_to_utf8(&self, index: usize) -> usize {
+        self.convert_index(index, char::len_utf16, char::len_utf8)
+    }
+
+    /// Converts `index` between two units, given the width of each `char` in
+    /// both units.
+    fn convert_index(
+        &self,
+        index: usize,
+        from: impl Fn(char) -> usize,
+        to: impl Fn(char) -> usize,
+    ) -> usize {
+        let (mut from_index, mut to_index) = (0, 0);
+        for c in self.chars() {
+            if from_index >= index {
+                break;
+            }
+            from_index += from(c);
+            to_index += to(c);
+        }
+        assert!(from_index == index, "index {} is out of bounds or inside a character", index);
+        to_index
+    }
+
+    fn visible_runs(&self) -> impl Iterator<Item = &Run<R>> {
+        self.runs.iter().filter(|run| !run.deleted)
+    }
+
+    /// Returns the run holding the character `id`, and its offset in the run.
+    fn find(&self, id: &ElementId<R>) -> Option<(usize, usize)> {
+        self.runs.iter()
+            .enumerate()
+            .find_map(|(i, run)| Some((i, run.offset_of(id)?)))
+    }
+
+    /// Splits the `i`-th run before `offset`, unless `offset` is at one of its
+    /// ends.
+    fn split(&mut self, i: usize, offset: usize) {
+        if offset > 0 && offset < self.runs[i].len() {
+            let tail = self.runs[i].split_off(offset);
+            self.runs.insert(i + 1, tail);
+        }
+    }
+
+    /// Joins the `i`-th run with the next one if it continues it.
+    fn try_join(&mut self, i: usize) {
+        if i + 1 < self.runs.len() && self.runs[i].continued_by(&self.runs[i + 1]) {
+            let next = self.runs.remove(i + 1);
+            self.runs[i].text.push_str(&next.text);
+        }
+    }
+
+    fn coalesce_around(&mut self, i: usize) {
+        self.try_join(i);
+        if i > 0 {
+            self.try_join(i - 1);
+        }
+    }
+
+    fn integrate(&mut self, id: ElementId<R>, after: Option<ElementId<R>>, text: String) {
+        if text.is_empty() || self.find(&id).is_some() {
+            return;
+        }
+        let (mut i, anchor) = match &after {
+            Some(after) => {
+                let (i, offset) = self.find(after).expect("insert after an unknown character");
+                self.split(i, offset + 1);
+                (i + 1, Some(i))
+            }
+            None => (0, None),
+        };
+        // Skip the characters inserted concurrently after the same character
+        // with a greater ID, along with everything inserted after them.
+        while i < self.runs.len() && self.runs[i].id > id {
+            i += 1;
+        }
+
+        let len = text.chars().count() as u64;
+        self.timestamp = max(self.timestamp, id.timestamp + len - 1);
+        self.runs.insert(i, Run { id, after, text, deleted: false });
+        self.coalesce_around(i);
+        // Rejoin the anchor's run if we split it but inserted further along.
+        if let Some(anchor) = anchor {
+            if anchor + 1 < i {
+                self.try_join(anchor);
+            }
+        }
+    }
+
+    fn delete_span(&mut self, span: &CharSpan<R>) {
+        let mut id = span.id.clone();
+        let end = span.id.timestamp + span.len as u64;
+        while id.timestamp < end {
+            let (mut i, offset) = self.find(&id).expect("delete of an unknown character");
+            if offset > 0 {
+                self.split(i, offset);
+                i += 1;
+            }
+            self.split(i, (end - id.timestamp) as usize);
+            self.runs[i].deleted = true;
+            id.timestamp += self.runs[i].len() as u64;
+            self.coalesce_around(i);
+        }
+    }
+
+    /// Returns the IDs of every character seen, and of the deleted ones.
+    fn ids(&self) -> (HashSet<ElementId<R>>, HashSet<ElementId<R>>) {
+        let mut seen = HashSet::new();
+        let mut deleted = HashSet::new();
+        for run in self.runs.iter() {
+            for offset in 0..run.len() {
+                seen.insert(run.char_id(offset));
+                if run.deleted {
+                    deleted.insert(run.char_id(offset));
+                }
+            }
+        }
+        (seen, deleted)
+    }
+}
+
+impl<R: ReplicaId> Default for Text<R> {
+    fn default() -> Text<R> {
+        Text::new()
+    }
+}
+
+impl<R: ReplicaId> fmt::Display for Text<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for run in self.visible_runs() {
+            f.write_str(&run.text)?;
+        }
+        Ok(())
+    }
+}
+
+/// An update to a `Text`, as requested at the source replica. Indexes count
+/// `char`s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextUpdate<R> {
+    Insert { index: usize, text: String, replica: R },
+    Delete { range: Range<usize> },
+}
+
+/// An operation on a `Text`: insert a string after a character, or delete
+/// spans of characters.
+///
+/// Serializes as an externally tagged enum: `Insert` wraps a struct with `id`,
+/// `after` and `text` fields, and `Delete` a struct with a `spans` field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum TextOp<R> {
+    Insert { id: ElementId<R>, after: Option<ElementId<R>>, text: String },
+    Delete { spans: Vec<CharSpan<R>> },
+}
+
+impl<R: ReplicaId> CmRDT for Text<R> {
+    type Update = TextUpdate<R>;
+    type Op = TextOp<R>;
+
+    /// # Panics
+    ///
+    /// Panics if the update's index or range is out of bounds.
+    fn prepare(&self, update: TextUpdate<R>) -> TextOp<R> {
+        match update {
+            TextUpdate::Insert { index, text, replica } => {
+                assert!(index <= self.len(), "insert index out of bounds");
+                let mut after = None;
+                let mut start = 0;
+                for run in self.visible_runs() {
+                    if start >= index {
+                        break;
+                    }
+                    let len = run.len();
+                    if index <= start + len {
+                        after = Some(run.char_id(index - start - 1));
+                    }
+                    start += len;
+                }
+                let id = ElementId { timestamp: self.timestamp + 1, replica };
+                TextOp::Insert { id, after, text }
+            }
+            TextUpdate::Delete { range } => {
+                assert!(
+                    range.start <= range.end && range.end <= self.len(),
+                    "delete range out of bounds"
+                );
+                let mut spans = vec![];
+                let mut start = 0;
+                for run in self.visible_runs() {
+                    let len = run.len();
+                    let from = max(range.start, start);
+                    let to = range.end.min(start + len);
+                    if from < to {
+                        spans.push(CharSpan { id: run.char_id(from - start), len: to - from });
+                    }
+                    start += len;
+                }
+                TextOp::Delete { spans }
+            }
+        }
+    }
+
+    /// # Panics
+    ///
+    /// Panics if the operation refers to a character this replica hasn't
+    /// seen, i.e. if operations weren't delivered in causal order.
+    fn effect(&mut self, op: TextOp<R>) {
+        match op {
+            TextOp::Insert { id, after, text } => self.integrate(id, after, text),
+            TextOp::Delete { spans } => {
+                for span in spans.iter() {
+                    self.delete_span(span);
+                }
+            }
+        }
+    }
+}
+
+impl<R: ReplicaId> CvRDT for Text<R> {
+    type Value = String;
+
+    fn value(&self) -> String {
+        self.to_string()
+    }
+
+    fn merge(&mut self, other: &Text<R>) {
+        for run in other.runs.iter() {
+            // A run may continue characters we already have, but once we find
+            // a character we don't have, we can't have any that follow it.
+            let len = run.len();
+            let mut offset = 0;
+            while offset < len {
+                let id = run.char_id(offset);
+                match self.find(&id) {
+                    Some((i, local_offset)) => {
+                        offset += (self.runs[i].len() - local_offset).min(len - offset);
+                    }
+                    None => {
+                        let (after, text) = match offset {
+                            0 => (run.after.clone(), run.text.clone()),
+                            _ => {
+                                let (byte, _) = run.text.char_indices().nth(offset).unwrap();
+                                (Some(run.char_id(offset - 1)), run.text[byte..].to_string())
+                            }
+                        };
+                        self.integrate(id, after, text);
+                        break;
+                    }
+                }
+            }
+            if run.deleted {
+                self.delete_span(&CharSpan { id: run.id.clone(), len });
+            }
+        }
+    }
+}
+
+impl<R: ReplicaId> PartialEq for Text<R> {
+    fn eq(&self, other: &Text<R>) -> bool {
+        self.partial_cmp(other) == Some(Ordering::Equal)
+    }
+}
+
+impl<R: ReplicaId> Eq for Text<R> {}
+
+impl<R: ReplicaId> PartialOrd for Text<R> {
+    /// Orders texts by the characters they have seen and the characters they
+    /// have deleted, regardless of how those are split into runs.
+    fn partial_cmp(&self, other: &Text<R>) -> Option<Ordering> {
+        let (seen, deleted) = self.ids();
+        let (other_seen, other_deleted) = other.ids();
+        combine_orderings(
+            subset_ordering(&seen, &other_seen),
+            subset_ordering(&deleted, &other_deleted),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::Rng;
+    #[cfg(feature = "serde")]
+    use crate::test_util::assert_roundtrip;
+
+    #[test]
+    fn test_text() {
+        let mut text_a = Text::new();
+        text_a.insert_str(0, "hello world", 1);
+        let mut text_b = text_a.clone();
+
+        let op_a = text_a.insert_str(5, ",", 1);
+        let op_b = text_b.insert_str(11, "!", 2);
+        let delete = text_b.delete_range(0..1);
+        let insert = text_b.insert_str(0, "H", 2);
+        text_a.effect(op_b);
+        text_a.effect(delete);
+        text_a.effect(insert);
+        text_b.effect(op_a);
+        assert_eq!(text_a.to_string(), "Hello, world!");
+        assert_eq!(text_a, text_b);
+        assert_eq!(text_a.len(), 13);
+
+        text_a.delete_range(5..12);
+        text_b.insert_str(7, "wide ", 2);
+        assert!(text_a.partial_cmp(&text_b).is_none());
+        text_a.merge(&text_b);
+        text_b.merge(&text_a);
+        assert_eq!(text_a, text_b);
+        assert_eq!(text_b.value(), "Hellowide !");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut text = Text::new();
+        text.insert_str(0, "héllo", 1u64);
+        let op = text.delete_range(1..3);
+        let json = serde_json::to_string(&op).unwrap();
+        assert_eq!(json, r#"{"Delete":{"spans":[{"id":{"timestamp":2,"replica":1},"len":2}]}}"#);
+        assert_roundtrip(&text);
+        assert_roundtrip(&op);
+    }
+
+    #[test]
+    fn test_runs() {
+        let mut text = Text::new();
+        for (i, c) in "typing".chars().enumerate() {
+            text.insert_str(i, &c.to_string(), 1);
+        }
+        assert_eq!(text.runs.len(), 1);
+
+        text.insert_str(3, "-", 2);
+        assert_eq!(text.runs.len(), 3);
+        text.delete_range(3..4);
+        assert_eq!(text.runs.len(), 3);
+
+        // Deleting neighbouring characters one by one leaves a single
+        // deleted run.
+        text.delete_range(1..2);
+        text.delete_range(1..2);
+        assert_eq!(text.to_string(), "ting");
+        assert_eq!(text.runs.len(), 4);
+
+        // A fresh replica merging the text ends up with the same runs.
+        let mut other = Text::new();
+        other.merge(&text);
+        assert_eq!(other.runs.len(), text.runs.len());
+        assert_eq!(other, text);
+    }
+
+    #[test]
+    fn test_index_conversions() {
+        let mut text = Text::new();
+        text.insert_str(0, "aé😀b", 1);
+        assert_eq!(text.len(), 4);
+
+        assert_eq!(text.char_to_utf8(3), 7);
+        assert_eq!(text.utf8_to_char(7), 3);
+        assert_eq!(text.char_to_utf16(3), 4);
+        assert_eq!(text.utf16_to_char(4), 3);
+        assert_eq!(text.utf8_to_utf16(3), 2);
+        assert_eq!(text.utf16_to_utf8(5), 8);
+        assert_eq!(text.char_to_utf16(0), 0);
+        assert_eq!(text.utf8_to_char(8), 4);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_index_inside_char() {
+        let mut text = Text::new();
+        text.insert_str(0, "😀", 1);
+        text.utf16_to_char(1);
+    }
+
+    /// Applies a random edit to `text`, returning its operation.
+    fn random_edit(text: &mut Text<usize>, replica: usize, rng: &mut Rng) -> TextOp<usize> {
+        let len = text.len();
+        if len == 0 || rng.next(3) > 0 {
+            let words = ["a", "bc", "déf", "😀", "ghij"];
+            text.insert_str(rng.next(len + 1), words[rng.next(words.len())], replica)
+        } else {
+            let start = rng.next(len);
+            text.delete_range(start..start + 1 + rng.next(len - start))
+        }
+    }
+
+    #[test]
+    fn test_random_ops_converge() {
+        let mut rng = Rng(0x2545_f491_4f6c_dd1d);
+        let mut texts = vec![Text::new(); 3];
+        for _ in 0..20 {
+            let mut queues: Vec<Vec<TextOp<usize>>> = vec![];
+            for (replica, text) in texts.iter_mut().enumerate() {
+                let edits = rng.next(5);
+                queues.push((0..edits).map(|_| random_edit(text, replica, &mut rng)).collect());
+            }
+            for (replica, text) in texts.iter_mut().enumerate() {
+                for (source, queue) in queues.iter().enumerate() {
+                    if source != replica {
+                        for op in queue.iter() {
+                            text.effect(op.clone());
+                        }
+                    }
+                }
+            }
+            assert_eq!(texts[0].to_string(), texts[1].to_string());
+            assert_eq!(texts[1].to_string(), texts[2].to_string());
+            assert_eq!(texts[0], texts[2]);
+        }
+    }
+
+    #[test]
+    fn test_random_merges_converge() {
+        let mut rng = Rng(0x9e37_79b9_7f4a_7c15);
+        let mut texts = vec![Text::new(); 4];
+        for _ in 0..100 {
+            let replica = rng.next(texts.len());
+            random_edit(&mut texts[replica], replica, &mut rng);
+            if rng.next(4) == 0 {
+                let (from, to) = (rng.next(texts.len()), rng.next(texts.len()));
+                let state = texts[from].clone();
+                texts[to].merge(&state);
+                assert!(state <= texts[to]);
+            }
+        }
+
+        let mut merged = Text::new();
+        for text in texts.iter() {
+            merged.merge(text);
+        }
+        for text in texts.iter_mut() {
+            text.merge(&merged);
+            assert_eq!(*text, merged);
+            assert_eq!(text.to_string(), merged.to_string());
+        }
+    }
+}
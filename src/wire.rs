@@ -0,0 +1,425 @@
+//! A compact, versioned binary encoding for counter states and deltas.
+//!
+//! Every message starts with a header:
+//!
+//! | size   | field                                          |
+//! |--------|------------------------------------------------|
+//! | 2      | magic bytes, `b"CR"`                           |
+//! | 1      | format version of the writer                   |
+//! | 1      | oldest format version able to read the message |
+//! | 1      | type tag (`GCOUNTER_TAG` or `PNCOUNTER_TAG`)   |
+//! | varint | payload length in bytes                        |
+//!
+//! The payload starts with a dictionary of the replica IDs mentioned in the
+//! message (a varint count followed by each ID), so that every entry can refer
+//! to its replica by dictionary index instead of repeating the ID. A counter
+//! body is a varint entry count followed by `(index, count)` varint pairs; a
+//! `PNCounter` body is the increment body followed by the decrement body.
+//! Integers are LEB128 varints throughout.
+//!
+//! Deltas are counter states themselves, so they are encoded exactly like
+//! full states.
+//!
+//! Readers skip any bytes a newer writer appends to the payload after the
+//! body, so fields can be added without breaking older replicas. Changes that
+//! older readers can't safely ignore must raise the minimum reader version,
+//! which older readers then reject with `DecodeError::UnsupportedVersion`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+use super::{GCounter, PNCounter, ReplicaId};
+
+/// The format version written by this crate.
+pub const VERSION: u8 = 1;
+
+/// The oldest reader version able to decode messages written by this crate.
+const MIN_READER_VERSION: u8 = 1;
+
+const MAGIC: [u8; 2] = *b"CR";
+
+/// Type tag of an encoded `GCounter`.
+pub const GCOUNTER_TAG: u8 = 1;
+
+/// Type tag of an encoded `PNCounter`.
+pub const PNCOUNTER_TAG: u8 = 2;
+
+/// The error returned when a message can't be decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended in the middle of a message.
+    UnexpectedEof,
+    /// The input doesn't start with the magic bytes.
+    BadMagic,
+    /// The message requires a newer reader than this one.
+    UnsupportedVersion { required: u8 },
+    /// The message encodes a different type than the one requested.
+    TypeMismatch { expected: u8, found: u8 },
+    /// A varint doesn't fit in a `u64`.
+    VarintOverflow,
+    /// A replica ID couldn't be decoded.
+    InvalidReplicaId,
+    /// An entry refers to an index past the end of the replica dictionary.
+    UnknownReplica(u64),
+    /// A replica appears twice in the dictionary or in a counter body.
+    DuplicateReplica,
+    /// The input continues after the end of the message.
+    TrailingBytes,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::UnexpectedEof => write!(f, "unexpected end of input"),
+            DecodeError::BadMagic => write!(f, "missing magic bytes"),
+            DecodeError::UnsupportedVersion { required } => {
+                write!(f, "message requires format version {}, this reader supports {}", required, VERSION)
+            }
+            DecodeError::TypeMismatch { expected, found } => {
+                write!(f, "expected type tag {}, found {}", expected, found)
+            }
+            DecodeError::VarintOverflow => write!(f, "varint overflows u64"),
+            DecodeError::InvalidReplicaId => write!(f, "invalid replica ID"),
+            DecodeError::UnknownReplica(index) => write!(f, "unknown replica index {}", index),
+            DecodeError::DuplicateReplica => write!(f, "duplicate replica"),
+            DecodeError::TrailingBytes => write!(f, "trailing bytes after message"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// A replica ID that can be written to the wire format.
+pub trait WireId: ReplicaId {
+    /// Appends the encoded ID to `buf`.
+    fn encode(&self, buf: &mut Vec<u8>);
+
+    /// Decodes an ID from the front of `input`, advancing past it.
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError>;
+}
+
+impl WireId for u32 {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_varint(buf, u64::from(*self));
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<u32, DecodeError> {
+        let id = read_varint(input)?;
+        if id > u64::from(u32::MAX) {
+            return Err(DecodeError::InvalidReplicaId);
+        }
+        Ok(id as u32)
+    }
+}
+
+impl WireId for u64 {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_varint(buf, *self);
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<u64, DecodeError> {
+        read_varint(input)
+    }
+}
+
+/// Encoded as 16 big-endian bytes, the usual layout for UUIDs.
+impl WireId for u128 {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&self.to_be_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<u128, DecodeError> {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(read_bytes(input, 16)?);
+        Ok(u128::from_be_bytes(bytes))
+    }
+}
+
+/// Encoded as a varint byte length followed by the UTF-8 bytes.
+impl WireId for String {
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_varint(buf, self.len() as u64);
+        buf.extend_from_slice(self.as_bytes());
+    }
+
+    fn decode(input: &mut &[u8]) -> Result<String, DecodeError> {
+        let len = read_varint(input)?;
+        if len > input.len() as u64 {
+            return Err(DecodeError::UnexpectedEof);
+        }
+        let bytes = read_bytes(input, len as usize)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidReplicaId)
+    }
+}
+
+/// A type with a wire format encoding.
+pub trait WireFormat: Sized {
+    /// Encodes `self` as a complete message.
+    fn to_bytes(&self) -> Vec<u8>;
+
+    /// Decodes a complete message, rejecting any input that doesn't hold
+    /// exactly one well-formed message of this type.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, DecodeError>;
+}
+
+impl<R: WireId> WireFormat for GCounter<R> {
+    fn to_bytes(&self) -> Vec<u8> {
+        let dictionary = Dictionary::new(self.counters.iter().map(|(id, _)| id));
+        let mut payload = vec![];
+        dictionary.encode(&mut payload);
+        encode_counts(self, &dictionary, &mut payload);
+        encode_message(GCOUNTER_TAG, &payload)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<GCounter<R>, DecodeError> {
+        let mut payload = decode_message(bytes, GCOUNTER_TAG)?;
+        let dictionary = decode_dictionary(&mut payload)?;
+        decode_counts(&mut payload, &dictionary)
+    }
+}
+
+impl<R: WireId> WireFormat for PNCounter<R> {
+    fn to_bytes(&self) -> Vec<u8> {
+        let ids = self.inc.counters.iter().chain(self.dec.counters.iter()).map(|(id, _)| id);
+        let dictionary = Dictionary::new(ids);
+        let mut payload = vec![];
+        dictionary.encode(&mut payload);
+        encode_counts(&self.inc, &dictionary, &mut payload);
+        encode_counts(&self.dec, &dictionary, &mut payload);
+        encode_message(PNCOUNTER_TAG, &payload)
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<PNCounter<R>, DecodeError> {
+        let mut payload = decode_message(bytes, PNCOUNTER_TAG)?;
+        let dictionary = decode_dictionary(&mut payload)?;
+        Ok(PNCounter {
+            inc: decode_counts(&mut payload, &dictionary)?,
+            dec: decode_counts(&mut payload, &dictionary)?,
+        })
+    }
+}
+
+/// Appends `value` to `buf` as an unsigned LEB128 varint.
+pub fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Reads an unsigned LEB128 varint from the front of `input`.
+pub fn read_varint(input: &mut &[u8]) -> Result<u64, DecodeError> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = read_bytes(input, 1)?[0];
+        let bits = u64::from(byte & 0x7f);
+        if shift == 63 && bits > 1 {
+            return Err(DecodeError::VarintOverflow);
+        }
+        value |= bits << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DecodeError::VarintOverflow)
+}
+
+fn read_bytes<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if input.len() < len {
+        return Err(DecodeError::UnexpectedEof);
+    }
+    let (bytes, rest) = input.split_at(len);
+    *input = rest;
+    Ok(bytes)
+}
+
+fn encode_message(tag: u8, payload: &[u8]) -> Vec<u8> {
+    let mut buf = MAGIC.to_vec();
+    buf.extend_from_slice(&[VERSION, MIN_READER_VERSION, tag]);
+    write_varint(&mut buf, payload.len() as u64);
+    buf.extend_from_slice(payload);
+    buf
+}
+
+/// Checks the header of a message and returns its payload.
+fn decode_message(mut input: &[u8], tag: u8) -> Result<&[u8], DecodeError> {
+    if read_bytes(&mut input, 2)? != MAGIC {
+        return Err(DecodeError::BadMagic);
+    }
+    let header = read_bytes(&mut input, 3)?;
+    let (min_reader_version, found) = (header[1], header[2]);
+    if min_reader_version > VERSION {
+        return Err(DecodeError::UnsupportedVersion { required: min_reader_version });
+    }
+    if found != tag {
+        return Err(DecodeError::TypeMismatch { expected: tag, found });
+    }
+    let len = read_varint(&mut input)?;
+    if len > input.len() as u64 {
+        return Err(DecodeError::UnexpectedEof);
+    }
+    if len < input.len() as u64 {
+        return Err(DecodeError::TrailingBytes);
+    }
+    Ok(input)
+}
+
+/// The replica IDs of a message being encoded, in ascending order.
+struct Dictionary<'a, R> {
+    ids: Vec<&'a R>,
+    indices: HashMap<&'a R, u64>,
+}
+
+impl<'a, R: WireId> Dictionary<'a, R> {
+    fn new(ids: impl Iterator<Item = &'a R>) -> Dictionary<'a, R> {
+        let ids: Vec<&R> = ids.collect::<BTreeSet<_>>().into_iter().collect();
+        let indices = ids.iter().enumerate().map(|(i, &id)| (id, i as u64)).collect();
+        Dictionary { ids, indices }
+    }
+
+    fn encode(&self, buf: &mut Vec<u8>) {
+        write_varint(buf, self.ids.len() as u64);
+        for id in &self.ids {
+            id.encode(buf);
+        }
+    }
+}
+
+fn decode_dictionary<R: WireId>(input: &mut &[u8]) -> Result<Vec<R>, DecodeError> {
+    let len = read_varint(input)?;
+    let mut ids = BTreeSet::new();
+    let mut dictionary = vec![];
+    for _ in 0..len {
+        let id = R::decode(input)?;
+        if !ids.insert(id.clone()) {
+            return Err(DecodeError::DuplicateReplica);
+        }
+        dictionary.push(id);
+    }
+    Ok(dictionary)
+}
+
+fn encode_counts<R: WireId>(counter: &GCounter<R>, dictionary: &Dictionary<R>, buf: &mut Vec<u8>) {
+    let mut entries: Vec<(u64, u64)> = counter.counters.iter()
+        .map(|(id, count)| (dictionary.indices[id], count))
+        .collect();
+    entries.sort_unstable();
+
+    write_varint(buf, entries.len() as u64);
+    for (index, count) in entries {
+        write_varint(buf, index);
+        write_varint(buf, count);
+    }
+}
+
+fn decode_counts<R: WireId>(input: &mut &[u8], dictionary: &[R]) -> Result<GCounter<R>, DecodeError> {
+    let mut counter = GCounter::new();
+    let len = read_varint(input)?;
+    for _ in 0..len {
+        let index = read_varint(input)?;
+        let count = read_varint(input)?;
+        let id = usize::try_from(index)
+            .ok()
+            .and_then(|index| dictionary.get(index))
+            .ok_or(DecodeError::UnknownReplica(index))?;
+        if counter.counters.insert(id.clone(), count).is_some() {
+            return Err(DecodeError::DuplicateReplica);
+        }
+    }
+    Ok(counter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::CvRDT;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut counter: PNCounter<String> = PNCounter::new();
+        counter.inc("a".to_string(), 300);
+        counter.dec("a".to_string(), 2);
+        counter.dec("b".to_string(), 1);
+
+        let bytes = counter.to_bytes();
+        assert_eq!(&bytes[..5], &[b'C', b'R', VERSION, 1, PNCOUNTER_TAG]);
+        assert_eq!(PNCounter::from_bytes(&bytes), Ok(counter.clone()));
+
+        let delta = counter.inc_delta("c".to_string(), 1);
+        assert_eq!(PNCounter::from_bytes(&delta.to_bytes()), Ok(delta));
+
+        let mut gcounter: GCounter<u128> = GCounter::new();
+        gcounter.inc(u128::MAX, u64::MAX);
+        let decoded = GCounter::from_bytes(&gcounter.to_bytes()).unwrap();
+        assert_eq!(decoded, gcounter);
+        assert_eq!(decoded.value(), u64::MAX);
+    }
+
+    #[test]
+    fn test_varint() {
+        for &value in &[0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut buf = vec![];
+            write_varint(&mut buf, value);
+            let mut input = &buf[..];
+            assert_eq!(read_varint(&mut input), Ok(value));
+            assert!(input.is_empty());
+        }
+
+        let mut input = &[0xff; 10][..];
+        assert_eq!(read_varint(&mut input), Err(DecodeError::VarintOverflow));
+        let mut input = &[0x80][..];
+        assert_eq!(read_varint(&mut input), Err(DecodeError::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let mut counter: GCounter<u64> = GCounter::new();
+        counter.inc(7, 1);
+        let bytes = counter.to_bytes();
+
+        assert_eq!(GCounter::<u64>::from_bytes(&bytes[..4]), Err(DecodeError::UnexpectedEof));
+        assert_eq!(GCounter::<u64>::from_bytes(b"XX\x01\x01\x01\x00"), Err(DecodeError::BadMagic));
+        assert_eq!(
+            PNCounter::<u64>::from_bytes(&bytes),
+            Err(DecodeError::TypeMismatch { expected: PNCOUNTER_TAG, found: GCOUNTER_TAG })
+        );
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(GCounter::<u64>::from_bytes(&trailing), Err(DecodeError::TrailingBytes));
+
+        // One replica (7) and one entry pointing at index 1.
+        let bad_index = encode_message(GCOUNTER_TAG, &[1, 7, 1, 1, 1]);
+        assert_eq!(GCounter::<u64>::from_bytes(&bad_index), Err(DecodeError::UnknownReplica(1)));
+
+        let duplicate = encode_message(GCOUNTER_TAG, &[2, 7, 7, 0]);
+        assert_eq!(GCounter::<u64>::from_bytes(&duplicate), Err(DecodeError::DuplicateReplica));
+
+        let bad_utf8 = encode_message(GCOUNTER_TAG, &[1, 1, 0xff, 0]);
+        assert_eq!(GCounter::<String>::from_bytes(&bad_utf8), Err(DecodeError::InvalidReplicaId));
+    }
+
+    #[test]
+    fn test_forward_compatibility() {
+        let mut counter: GCounter<u64> = GCounter::new();
+        counter.inc(7, 1);
+
+        // A newer writer that appends a field older readers don't know about.
+        let mut payload = vec![1, 7, 1, 0, 1];
+        payload.extend_from_slice(&[0xde, 0xad]);
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&[VERSION + 1, VERSION, GCOUNTER_TAG, payload.len() as u8]);
+        bytes.extend_from_slice(&payload);
+        assert_eq!(GCounter::from_bytes(&bytes), Ok(counter));
+
+        // A newer writer whose messages older readers can't interpret.
+        bytes[3] = VERSION + 1;
+        assert_eq!(
+            GCounter::<u64>::from_bytes(&bytes),
+            Err(DecodeError::UnsupportedVersion { required: VERSION + 1 })
+        );
+    }
+}